@@ -0,0 +1,17 @@
+#define_import_path portfolio::blue_noise
+
+// Shader side of noise::blue_noise_rotate/blue_noise_offset: a baked blue noise
+// texture looked up per pixel, moved by the R2 sequence and shifted by the golden
+// ratio every frame so the jitter averages out instead of banding.
+
+const GOLDEN_RATIO_CONJUGATE: f32 = 0.61803398875;
+const R2: vec2<f32> = vec2<f32>(0.7548777, 0.5698403);
+
+fn blue_noise(tex: texture_2d<f32>, frag_coord: vec2<f32>, frame: u32) -> f32 {
+    // wrapped so the f32 products stay exact enough
+    let f = f32(frame % 4096u);
+    let size = vec2<i32>(textureDimensions(tex));
+    let offset = vec2<i32>(fract(R2 * f) * vec2<f32>(size));
+    let texel = (vec2<i32>(frag_coord) + offset) % size;
+    return fract(textureLoad(tex, texel, 0).x + f * GOLDEN_RATIO_CONJUGATE);
+}
@@ -1,3 +1,6 @@
+#import portfolio::noise_warp
+#import portfolio::blue_noise
+
 
 struct Material {
     sun_direction: vec3<f32>,
@@ -11,6 +14,17 @@ struct Material {
     cloud_coef: f32,
     cloud_height: f32,
     scroll: f32,
+    flow_strength: f32,
+    value_warp: f32,
+    frame_count: u32,
+    anim_fps: f32,
+    anim_mix: f32,
+    shape_factor: f32,
+    frame: u32,
+    shadow_jitter: f32,
+    sdf_factor: f32,
+    worley_range: vec2<f32>,
+    value_range: vec2<f32>,
 };
 
 @group(1) @binding(0)
@@ -23,6 +37,34 @@ var w_sampler: sampler;
 var v_tex: texture_2d<f32>;
 @group(1) @binding(4)
 var v_sampler: sampler;
+@group(1) @binding(5)
+var shape_tex: texture_3d<f32>;
+@group(1) @binding(6)
+var shape_sampler: sampler;
+@group(1) @binding(7)
+var flow_tex: texture_3d<f32>;
+@group(1) @binding(8)
+var flow_sampler: sampler;
+@group(1) @binding(9)
+var anim_tex: texture_2d_array<f32>;
+@group(1) @binding(10)
+var anim_sampler: sampler;
+@group(1) @binding(11)
+var detail_tex: texture_3d<f32>;
+@group(1) @binding(12)
+var detail_sampler: sampler;
+@group(1) @binding(13)
+var blue_noise_tex: texture_2d<f32>;
+@group(1) @binding(14)
+var sdf_tex: texture_3d<f32>;
+@group(1) @binding(15)
+var sdf_sampler: sampler;
+
+// Screen space derivatives of the cloud uv, taken once at the top of the fragment
+// shader. The cloud is sampled with them instead of implicit derivatives, so it can be
+// evaluated in the per-pixel shadow loop.
+var<private> uv_dx: vec2<f32>;
+var<private> uv_dy: vec2<f32>;
 
 fn step(a: f32, b: f32, t: f32) -> f32 {
     let x = t - a;
@@ -30,25 +72,89 @@ fn step(a: f32, b: f32, t: f32) -> f32 {
     return clamp(0., 1., y);
 }
 
+// The worley and value textures may be quantised to [0, 1], see bake::Encoded::range
+fn decode(t: f32, range: vec2<f32>) -> f32 {
+    return range.x + t * (range.y - range.x);
+}
+
+// `scale` is how much faster than the cloud uv `uv` moves
+fn value_at(uv: vec2<f32>, scale: f32) -> f32 {
+    let t = textureSampleGrad(v_tex, v_sampler, uv, uv_dx * scale, uv_dy * scale).x;
+    return decode(t, material.value_range);
+}
+
 fn sabs(x: f32, j: f32) -> f32 {
     return sqrt(x * x + j);
 }
 
+// Worley texture advected along the curl noise flow field, two phases half a
+// cycle apart are blended so the distortion never builds up
+fn advected_worley(p: vec2<f32>) -> f32 {
+    let flow_dx = vec3(uv_dx * 0.5, 0.);
+    let flow_dy = vec3(uv_dy * 0.5, 0.);
+    let v = textureSampleGrad(flow_tex, flow_sampler, vec3(p * 0.5, material.time * 0.002), flow_dx, flow_dy).xz * material.flow_strength;
+    let t = material.time * 0.05;
+    let phase_a = fract(t);
+    let phase_b = fract(t + 0.5);
+    let a = decode(textureSampleGrad(w_tex, w_sampler, p - v * phase_a, uv_dx, uv_dy).x, material.worley_range);
+    let b = decode(textureSampleGrad(w_tex, w_sampler, p - v * phase_b + 0.5, uv_dx, uv_dy).x, material.worley_range);
+    return mix(b, a, 1. - abs(1. - 2. * phase_a));
+}
+
+// Looping animation baked into the layers of anim_tex, blended between frames
+fn animated_detail(p: vec2<f32>) -> f32 {
+    let f = material.time * material.anim_fps;
+    let frame = u32(floor(f)) % material.frame_count;
+    let next = (frame + 1u) % material.frame_count;
+    let a = textureSampleGrad(anim_tex, anim_sampler, p, i32(frame), uv_dx, uv_dy).x;
+    let b = textureSampleGrad(anim_tex, anim_sampler, p, i32(next), uv_dx, uv_dy).x;
+    return mix(a, b, fract(f));
+}
+
+fn remap(x: f32, a: f32, b: f32, c: f32, d: f32) -> f32 {
+    return (x - a) / (b - a) * (d - c) + c;
+}
+
+// Perlin-Worley base shape, its edges eroded by the detail volume
+fn shape_density(p: vec2<f32>) -> f32 {
+    let uvw = vec3(p * 0.25, material.time * 0.001);
+    let uvw_dx = vec3(uv_dx * 0.25, 0.);
+    let uvw_dy = vec3(uv_dy * 0.25, 0.);
+    let shape = textureSampleGrad(shape_tex, shape_sampler, uvw, uvw_dx, uvw_dy);
+    let low = dot(shape.yzw, vec3(0.625, 0.25, 0.125));
+    let base = clamp(remap(shape.x, low - 1., 1., 0., 1.), 0., 1.);
+    let detail = dot(textureSampleGrad(detail_tex, detail_sampler, uvw * 4., uvw_dx * 4., uvw_dy * 4.).xyz, vec3(0.625, 0.25, 0.125));
+    return clamp(remap(base, detail * 0.3, 1., 0., 1.), 0., 1.);
+}
+
+// Thins the cloud out away from the shape in sdf_tex, the slice 0.2 up (where
+// cloud.sdf.ron centres it) laid over the plane. The distances are baked over -1 to 1,
+// one uv across, and clamp past it.
+fn shape_mask(p: vec2<f32>) -> f32 {
+    let uvw = vec3(p.x, 0.6, p.y);
+    let d = textureSampleGrad(sdf_tex, sdf_sampler, uvw, vec3(uv_dx.x, 0., uv_dx.y), vec3(uv_dy.x, 0., uv_dy.y)).x;
+    return material.sdf_factor * smoothstep(0., 0.3, d);
+}
+
 fn cloud(p: vec2<f32>) -> f32 {
     let g = sabs(length(p) - 0.8 + sin(material.time) * 0.2, 0.001) + 0.7 ;
-    let w = (textureSample(w_tex, w_sampler, p - material.time * 0.01).x) - material.worley_factor  ;
-    let z = textureSample(v_tex, v_sampler, p + material.time * vec2(0.01, -0.01)).x - material.value_factor ;
-    return z * (1. + w) * material.cloud_coef - step(0.8, 1.6, g)   ;
+    let w = advected_worley(p) - material.worley_factor  ;
+    let vp = domain_warp(v_tex, v_sampler, p + material.time * vec2(0.01, -0.01), 5., material.value_range, material.value_warp, 1);
+    let z = mix(value_at(vp, 1.), animated_detail(p), material.anim_mix) * mix(1., shape_density(p), material.shape_factor) - material.value_factor ;
+    return z * (1. + w) * material.cloud_coef - step(0.8, 1.6, g) - shape_mask(p)   ;
 }
 
 
 
 @fragment
 fn fragment(
+    @builtin(position) frag_coord: vec4<f32>,
     #import bevy_pbr::mesh_vertex_output
 ) -> @location(0) vec4<f32> {
     let sun_dir = material.sun_direction  ;
     var p = uv + material.scroll * vec2(0., 1.0);
+    uv_dx = dpdx(p);
+    uv_dy = dpdy(p);
     let samp = cloud(p);
     let samps = cloud(p + sun_dir.xz * 0.001);
     let sampd = samps - samp;
@@ -62,7 +168,9 @@ fn fragment(
         h = minh;
         p -= sun_dir.xz * 0.02  ;
     }
-    for (var d = 0.1; d < material.shadow_dist; d += d) {
+    // start somewhere in the first step, the steps double so this shifts all of them
+    let jitter = blue_noise(blue_noise_tex, frag_coord.xy, material.frame) * material.shadow_jitter;
+    for (var d = 0.1 * (1. + jitter); d < material.shadow_dist; d += d) {
         let s = cloud(p - sun_dir.xz * d * 0.001);
         maxh = max(maxh, s);
         let u = s - sun_dir.y * d * 0.001 - h;
@@ -83,11 +191,11 @@ fn fragment(
         let pd = (sun_dir.xz * 0.00001 + p);
         let rd = normalize(world_position.xyz - material.camera_position);
         let sun = sun_dir * vec3(-1., 1., 1.);
-        let noi = 2.0 - 1.5 * abs(textureSample(v_tex, v_sampler, (p + material.time * 0.02)) * textureSample(v_tex, v_sampler, (p - material.time * 0.02 + vec2(1.123, 1.33123))) - 0.1) ;
-        let noid = 2.0 - 1.5 * abs(textureSample(v_tex, v_sampler, (pd + material.time * 0.02)) * textureSample(v_tex, v_sampler, (pd - material.time * 0.02 + vec2(1.123, 1.33123))) - 0.1) ;
+        let noi = 2.0 - 1.5 * abs(value_at(p + material.time * 0.02, 4.) * value_at(p - material.time * 0.02 + vec2(1.123, 1.33123), 4.) - 0.1) ;
+        let noid = 2.0 - 1.5 * abs(value_at(pd + material.time * 0.02, 4.) * value_at(pd - material.time * 0.02 + vec2(1.123, 1.33123), 4.) - 0.1) ;
         let s = (noi - noid) * 1000.;
-        let shine = pow(max(0.0, dot(rd, sun) * 0.03 + s.x * 0.5), 2.5) * sha ;
-        water = 1000.0 * shine + vec3(0.01, 0.02, 0.1) + 1.5 * vec3(0.06, 0.15, 0.12) * smoothstep(-0.4, 1., -s.x) * max(0., -noi.x + 2.5);
+        let shine = pow(max(0.0, dot(rd, sun) * 0.03 + s * 0.5), 2.5) * sha ;
+        water = 1000.0 * shine + vec3(0.01, 0.02, 0.1) + 1.5 * vec3(0.06, 0.15, 0.12) * smoothstep(-0.4, 1., -s) * max(0., -noi + 2.5);
     }
 
 
@@ -3,6 +3,7 @@ struct CustomMaterial {
     camera_position: vec3<f32>,
     scale: vec3<f32>,
     time: f32,
+    noise_range: vec2<f32>,
 };
 
 @group(1) @binding(0)
@@ -12,6 +13,11 @@ var noise_texture: texture_3d<f32>;
 @group(1) @binding(2)
 var noise_sampler: sampler;
 
+// the volume is quantised to [0, 1], see bake::Encoded::range
+fn decode(t: f32) -> f32 {
+    return material.noise_range.x + t * (material.noise_range.y - material.noise_range.x);
+}
+
 
 fn mie(costh: f32) -> f32 {
     let params = array(9.805233e-06, -6.500000e+01, -5.500000e+01, 8.194068e-01, 1.388198e-01, -8.370334e+01, 7.810083e+00, 2.054747e-03, 2.600563e-02, -4.552125e-12);
@@ -36,8 +42,8 @@ fn fragment(
     let ray_direction = normalize(world_position.xyz - material.camera_position);
     var sample_position = world_position.xyz * 0.009   ;
     let normal = normalize(world_normal.xyz);
-    let noise = textureSample(noise_texture, noise_sampler, abs(fract(0.12 * sample_position) - 0.5) * 2.).x;
-    let dxnoise = textureSample(noise_texture, noise_sampler, abs(fract(0.12 * (sample_position - vec3(0., 10., 0.) - ray_direction * 3.)) - 0.5) * 2.).x;
+    let noise = decode(textureSample(noise_texture, noise_sampler, abs(fract(0.12 * sample_position) - 0.5) * 2.).x);
+    let dxnoise = decode(textureSample(noise_texture, noise_sampler, abs(fract(0.12 * (sample_position - vec3(0., 10., 0.) - ray_direction * 3.)) - 0.5) * 2.).x);
     let sun_dir = normalize(material.sun_direction * vec3(-1., -1., 1.));
     let mie_signal = mie(dot(ray_direction, sun_dir)) ;
     let sun_color = vec3(1.1, 1.1, 1.) ;
@@ -41,66 +41,6 @@ fn mie(costh: f32) -> f32 {
     return dot(expValues, expValWeight) * 0.25;
 }
 
-fn hash(p: vec3<f32>) -> f32 {
-    // replace this by something better {
-    var p = fract(p * 0.3183099 + 0.1);
-    p *= 17.0;
-    return fract(p.x * p.y * p.z * (p.x + p.y + p.z));
-}
-
-fn value_noise(x: vec3<f32>) -> vec4<f32> {
-    let i = floor(x);
-    let w = fract(x);
-
-    let u = w * w * w * (w * (w * 6.0 - 15.0) + 10.0);
-    let du = 30.0 * w * w * (w * (w - 2.0) + 1.0);    let a = hash(i + vec3(0.0, 0.0, 0.0));
-    let b = hash(i + vec3(1.0, 0.0, 0.0));
-    let c = hash(i + vec3(0.0, 1.0, 0.0));
-    let d = hash(i + vec3(1.0, 1.0, 0.0));
-    let e = hash(i + vec3(0.0, 0.0, 1.0));
-    let f = hash(i + vec3(1.0, 0.0, 1.0));
-    let g = hash(i + vec3(0.0, 1.0, 1.0));
-    let h = hash(i + vec3(1.0, 1.0, 1.0));
-
-    let k0 = a;
-    let k1 = b - a;
-    let k2 = c - a;
-    let k3 = e - a;
-    let k4 = a - b - c + d;
-    let k5 = a - c - e + g;
-    let k6 = a - b - e + f;
-    let k7 = -a + b + c - d + e - f - g + h;
-
-    let deriv = du * vec3(
-        k1 + k4 * u.y + k6 * u.z + k7 * u.y * u.z,
-        k2 + k5 * u.z + k4 * u.x + k7 * u.z * u.x,
-        k3 + k6 * u.x + k5 * u.y + k7 * u.x * u.y,
-    );
-    return vec4(
-        k0 + k1 * u.x + k2 * u.y + k3 * u.z + k4 * u.x * u.y + k5 * u.y * u.z + k6 * u.z * u.x + k7 * u.x * u.y * u.z,
-        deriv.x,
-        deriv.y,
-        deriv.z,
-    );
-}
-
-fn value_fbm(p: vec3<f32>) -> vec4<f32 > {
-    var p = p ;
-    var t = vec4(0.);
-    var s = 1.;
-    var c = 1.;
-
-    for (var i = 0; i < 3 ; i++) {
-        p += vec3(13.123, -72., 234.23);
-        t += (value_noise(p * s)) * c;
-        s *= 2.;
-        c *= 0.5;
-    }
-    return t / 2.7;
-}
-
-
-
 @fragment
 fn fragment(
     #import bevy_pbr::mesh_vertex_output
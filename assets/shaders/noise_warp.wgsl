@@ -0,0 +1,26 @@
+#define_import_path portfolio::noise_warp
+
+// Shader side of noise::Warp with a single warp field baked into a 2D texture.
+// `scale` is the number of noise units the texture covers, `strength` is in noise
+// units like WarpSettings::strength and `range` is the decode range the texture was
+// quantised with (proctex::DecodeRange), vec2(0., 1.) for a float texture. Uses
+// textureSampleLevel so it can be called from loops and branches.
+
+// noise::WARP_OFFSETS, the y displacement is read this far from the x one
+const WARP_OFFSET: vec2<f32> = vec2<f32>(5.2, 1.3);
+
+fn warp_displacement(field: texture_2d<f32>, field_sampler: sampler, uv: vec2<f32>, scale: f32, range: vec2<f32>) -> vec2<f32> {
+    let t = vec2(
+        textureSampleLevel(field, field_sampler, uv, 0.).x,
+        textureSampleLevel(field, field_sampler, uv + WARP_OFFSET / scale, 0.).x,
+    );
+    return (range.x + t * (range.y - range.x)) * 2. - 1.;
+}
+
+fn domain_warp(field: texture_2d<f32>, field_sampler: sampler, uv: vec2<f32>, scale: f32, range: vec2<f32>, strength: f32, iterations: i32) -> vec2<f32> {
+    var q = uv;
+    for (var i = 0; i < iterations; i++) {
+        q = uv + strength / scale * warp_displacement(field, field_sampler, q, scale, range);
+    }
+    return q;
+}
@@ -1,3 +1,5 @@
+#import portfolio::blue_noise
+
 struct CustomMaterial {
     color: vec4<f32>,
     camera_position: vec3<f32>,
@@ -5,6 +7,7 @@ struct CustomMaterial {
     texture_dim: vec3<f32>,
     scale: vec3<f32>,
     time: f32,
+    frame: u32,
 };
 
 fn rayleigh(costh: f32) -> f32 {
@@ -47,6 +50,8 @@ var<uniform> material: CustomMaterial;
 var volume_tex: texture_3d<f32>;
 @group(1) @binding(2)
 var volume_sampler: sampler;
+@group(1) @binding(3)
+var blue_noise_tex: texture_2d<f32>;
 
 // @location(0) world_position: vec4<f32>,
 // @location(1) world_normal: vec3<f32>,
@@ -113,6 +118,7 @@ fn tpow(x: f32) -> f32{
 
 @fragment
 fn fragment(
+    @builtin(position) frag_coord: vec4<f32>,
     #import bevy_pbr::mesh_vertex_output
 ) -> @location(0) vec4<f32> {
     let ro = material.camera_position - material.aabb_position;
@@ -128,7 +134,7 @@ fn fragment(
     var light = vec3(0.);
     var absorbtion = 0.;
     let intersection = boxIntersection(ro, rd, vec3(0.5)*material.scale);
-    var i = max(intersection.x, 0.) + hash13(vec3(uv * 913.123, material.time )) * dt;
+    var i = max(intersection.x, 0.) + blue_noise(blue_noise_tex, frag_coord.xy, material.frame) * dt;
     for (; i < intersection.y; i += dt) {
         // if absorbtion > 4. {
         //     absorbtion = 6.;
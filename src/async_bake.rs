@@ -0,0 +1,247 @@
+//! Bakes images on the `AsyncComputeTaskPool` so large textures don't stall startup.
+//!
+//! The handle is usable right away: it holds a cheap placeholder (usually the same noise
+//! at a lower resolution) until the bake finishes, then the image is replaced in place.
+//! Materials keep the same handle and pick up the new image on their next update.
+//!
+//! Quantised images (see [`bake::TexelEncoding`]) also record the range their values were
+//! remapped from, which materials read back with [`PendingBakes::range`].
+
+use bevy::{
+    math::vec2,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension},
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::HashMap,
+};
+use futures_lite::future;
+
+use crate::bake;
+
+/// A baked image and the range its texels decode to, see [`bake::Encoded`].
+pub struct Baked {
+    pub image: Image,
+    pub range: Vec2,
+}
+
+impl From<Image> for Baked {
+    fn from(image: Image) -> Self {
+        Self {
+            image,
+            range: vec2(0.0, 1.0),
+        }
+    }
+}
+
+impl Baked {
+    pub fn new(size: Extent3d, dimension: TextureDimension, encoded: bake::Encoded) -> Self {
+        Self {
+            image: Image::new(size, dimension, encoded.bytes, encoded.format),
+            range: encoded.range,
+        }
+    }
+}
+
+/// Bakes still running, each replaces its image when it finishes, and the decode range
+/// of every image baked so far.
+#[derive(Resource, Default)]
+pub struct PendingBakes {
+    tasks: Vec<(Handle<Image>, Task<Option<Baked>>)>,
+    ranges: HashMap<Handle<Image>, Vec2>,
+}
+
+impl PendingBakes {
+    /// Adds `placeholder` and starts baking the image that replaces it.
+    pub fn add<B: Into<Baked>>(
+        &mut self,
+        images: &mut Assets<Image>,
+        placeholder: impl Into<Baked>,
+        bake: impl FnOnce() -> B + Send + 'static,
+    ) -> Handle<Image> {
+        let placeholder = placeholder.into();
+        let handle = images.add(placeholder.image);
+        self.ranges.insert(handle.clone_weak(), placeholder.range);
+        self.replace(handle.clone(), bake);
+        return handle;
+    }
+
+    /// Bakes a new image for an existing handle, which keeps its current image until
+    /// then. Cancels a bake that's still running for the same handle so a stale result
+    /// can't land after this one.
+    pub fn replace<B: Into<Baked>>(
+        &mut self,
+        handle: Handle<Image>,
+        bake: impl FnOnce() -> B + Send + 'static,
+    ) {
+        self.try_replace(handle, move || Ok::<_, String>(bake()));
+    }
+
+    /// [`PendingBakes::replace`] with a bake that can fail, the image is left as it is
+    /// and the error logged then.
+    pub fn try_replace<B: Into<Baked>, E: std::fmt::Display>(
+        &mut self,
+        handle: Handle<Image>,
+        bake: impl FnOnce() -> Result<B, E> + Send + 'static,
+    ) {
+        self.cancel(&handle);
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            match bake() {
+                Ok(baked) => Some(baked.into()),
+                Err(e) => {
+                    error!("bake failed: {}", e);
+                    None
+                }
+            }
+        });
+        self.tasks.push((handle, task));
+    }
+
+    /// Drops the bake running for `handle`, if any, so it never replaces the image.
+    pub fn cancel(&mut self, handle: &Handle<Image>) {
+        self.tasks.retain(|(pending, _)| pending != handle);
+    }
+
+    /// Records the range of an image baked elsewhere, like a loaded `.proctex.ron`.
+    pub fn set_range(&mut self, handle: &Handle<Image>, range: Vec2) {
+        self.ranges.insert(handle.clone_weak(), range);
+    }
+
+    pub fn is_baking(&self, handle: &Handle<Image>) -> bool {
+        self.tasks.iter().any(|(pending, _)| pending == handle)
+    }
+
+    /// What the texels of `handle` decode to, (0, 1) for images baked elsewhere.
+    pub fn range(&self, handle: &Handle<Image>) -> Vec2 {
+        self.ranges.get(handle).copied().unwrap_or(vec2(0.0, 1.0))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+}
+
+fn finish_bakes(mut pending: ResMut<PendingBakes>, mut images: ResMut<Assets<Image>>) {
+    let PendingBakes { tasks, ranges } = &mut *pending;
+    tasks.retain_mut(|(handle, task)| {
+        let Some(baked) = future::block_on(future::poll_once(task)) else {
+            return true;
+        };
+        let Some(baked) = baked else {
+            return false;
+        };
+        if let Some(target) = images.get_mut(handle) {
+            *target = baked.image;
+            ranges.insert(handle.clone_weak(), baked.range);
+        }
+        return false;
+    });
+}
+
+/// Runs the [`PendingBakes`] and swaps their results in.
+pub struct AsyncBakePlugin;
+
+impl Plugin for AsyncBakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingBakes>();
+        app.add_system(finish_bakes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use bevy::render::render_resource::TextureFormat;
+
+    use super::*;
+
+    fn image(size: u32, value: f32) -> Image {
+        return Image::new_fill(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &value.to_ne_bytes(),
+            TextureFormat::R32Float,
+        );
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Image>()
+            .add_plugin(AsyncBakePlugin);
+        return app;
+    }
+
+    // steps the app until nothing is baking, the bakes run on other threads
+    fn finish(app: &mut App) {
+        for _ in 0..1000 {
+            app.update();
+            if app.world.resource::<PendingBakes>().is_empty() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("the bakes never finished");
+    }
+
+    #[test]
+    fn placeholders_are_swapped_for_the_bake() {
+        let mut app = app();
+        let (done, wait) = mpsc::channel();
+        let handle = app
+            .world
+            .resource_scope(|world, mut pending: Mut<PendingBakes>| {
+                let mut images = world.resource_mut::<Assets<Image>>();
+                return pending.add(&mut images, image(1, 0.5), move || {
+                    wait.recv().unwrap();
+                    return Baked {
+                        image: image(4, 2.0),
+                        range: vec2(-1.0, 3.0),
+                    };
+                });
+            });
+
+        app.update();
+        let placeholder = app.world.resource::<Assets<Image>>().get(&handle).unwrap();
+        assert_eq!(placeholder.size(), vec2(1.0, 1.0));
+        let pending = app.world.resource::<PendingBakes>();
+        assert!(pending.is_baking(&handle));
+        assert_eq!(pending.range(&handle), vec2(0.0, 1.0));
+
+        done.send(()).unwrap();
+        finish(&mut app);
+        let baked = app.world.resource::<Assets<Image>>().get(&handle).unwrap();
+        assert_eq!(baked.data, image(4, 2.0).data);
+        assert_eq!(
+            app.world.resource::<PendingBakes>().range(&handle),
+            vec2(-1.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn failed_and_cancelled_bakes_keep_the_image() {
+        let mut app = app();
+        let handle = app.world.resource_mut::<Assets<Image>>().add(image(1, 0.5));
+        let (done, wait) = mpsc::channel();
+        let mut pending = app.world.resource_mut::<PendingBakes>();
+        pending.replace(handle.clone(), move || {
+            wait.recv().unwrap();
+            return image(4, 2.0);
+        });
+        pending.cancel(&handle);
+        pending.try_replace(handle.clone(), || Err::<Image, _>("no"));
+        done.send(()).unwrap();
+        finish(&mut app);
+        let image = app.world.resource::<Assets<Image>>().get(&handle).unwrap();
+        assert_eq!(image.size(), vec2(1.0, 1.0));
+    }
+}
@@ -0,0 +1,469 @@
+//! Bakes any [`NoiseFn`] into texture data.
+//!
+//! Texels are laid out row by row (then slice by slice) the way `Image::new` expects.
+//! With `tile` set the baked domain is rounded to whole noise cells and used as the
+//! period, so the texture repeats seamlessly under a `Repeat` sampler.
+//!
+//! Baking is split into fixed chunks of texels run on the rayon pool. Every chunk writes
+//! its own slice of the output, so the result is the same whatever the thread count.
+
+use bevy::{
+    math::{vec2, vec3, vec4},
+    prelude::*,
+    render::{render_resource::TextureFormat, settings::WgpuFeatures},
+};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::noise::{self, NoiseFn};
+
+/// Texels per parallel work item.
+pub const CHUNK_SIZE: usize = 4096;
+
+/// Samples `noise` over `scale` units in x and y at `z = 0`.
+pub fn noise_texture_2d(
+    buffer_dimensions: (usize, usize),
+    scale: Vec2,
+    tile: bool,
+    noise: &impl NoiseFn,
+) -> Vec<f32> {
+    Grid::new_2d(buffer_dimensions, scale, tile).sample_noise(noise)
+}
+
+/// Samples `noise` over `scale` units on every axis.
+pub fn noise_texture_3d(
+    buffer_dimensions: (usize, usize, usize),
+    scale: Vec3,
+    tile: bool,
+    noise: &impl NoiseFn,
+) -> Vec<f32> {
+    Grid::new_3d(buffer_dimensions, scale, tile).sample_noise(noise)
+}
+
+/// Like [`noise_texture_3d`] for a vector field, padded to RGBA for an `Rgba32Float` image.
+pub fn vector_texture_3d(
+    buffer_dimensions: (usize, usize, usize),
+    scale: Vec3,
+    tile: bool,
+    field: impl Fn(Vec3, Option<Vec3>) -> Vec3 + Sync,
+) -> Vec<Vec4> {
+    sample_grid_3d(buffer_dimensions, scale, tile, |p, period| {
+        field(p, period).extend(0.0)
+    })
+}
+
+/// Like [`noise_texture_3d`] for a noise with four channels, for an `Rgba32Float` image.
+pub fn rgba_texture_3d(
+    buffer_dimensions: (usize, usize, usize),
+    scale: Vec3,
+    tile: bool,
+    field: impl Fn(Vec3, Option<Vec3>) -> Vec4 + Sync,
+) -> Vec<Vec4> {
+    sample_grid_3d(buffer_dimensions, scale, tile, field)
+}
+
+/// Bakes `frames` layers of a looping 2D animation for a `2d_array` texture.
+/// `noise4(point, period)` is sampled with time on a circle (see [`noise::loop_point`]),
+/// x and y tile over `scale` units.
+pub fn looping_texture_array(
+    buffer_dimensions: (usize, usize),
+    frames: usize,
+    scale: Vec2,
+    radius: f32,
+    noise4: impl Fn(Vec4, Vec4) -> f32 + Sync,
+) -> Vec<f32> {
+    let scale = noise::tile_period(scale.extend(1.0)).truncate();
+    let period = vec4(scale.x, scale.y, f32::INFINITY, f32::INFINITY);
+    (0..frames)
+        .flat_map(|frame| {
+            let t = frame as f32 / frames as f32;
+            sample_grid_2d(buffer_dimensions, scale, false, |p, _| {
+                noise4(noise::loop_point(p.truncate(), t, radius), period)
+            })
+        })
+        .collect()
+}
+
+/// A looping 2D animation stored as a volume with time along z, so a `Repeat`
+/// sampler wraps the loop and filters between frames. `noise4` has to repeat
+/// every `time_period` units along w for the loop to be seamless.
+pub fn looping_volume(
+    buffer_dimensions: (usize, usize),
+    frames: usize,
+    scale: Vec2,
+    time_period: f32,
+    noise4: impl Fn(Vec4, Vec4) -> f32 + Sync,
+) -> Vec<f32> {
+    let scale = noise::tile_period(scale.extend(1.0)).truncate();
+    let time_period = time_period.round().max(1.0);
+    let period = vec4(scale.x, scale.y, f32::INFINITY, time_period);
+    sample_grid_3d(
+        (buffer_dimensions.0, buffer_dimensions.1, frames),
+        scale.extend(1.0),
+        false,
+        |p, _| {
+            noise4(
+                noise::periodic_time_point(p.truncate().extend(0.0), p.z, time_period),
+                period,
+            )
+        },
+    )
+}
+
+/// Fills `len` texels in parallel, `fill(start, chunk)` writes texels `start..start + chunk.len()`.
+pub fn par_fill<T: Clone + Default + Send>(
+    len: usize,
+    fill: impl Fn(usize, &mut [T]) + Sync,
+) -> Vec<T> {
+    let mut data = vec![T::default(); len];
+    data.par_chunks_mut(CHUNK_SIZE)
+        .enumerate()
+        .for_each(|(i, chunk)| fill(i * CHUNK_SIZE, chunk));
+    data
+}
+
+// Texel positions in noise space and the period to sample them with
+struct Grid {
+    dimensions: (usize, usize, usize),
+    resolution: Vec3,
+    scale: Vec3,
+    period: Option<Vec3>,
+}
+
+impl Grid {
+    fn new_2d(buffer_dimensions: (usize, usize), scale: Vec2, tile: bool) -> Grid {
+        let (scale, period) = if tile {
+            let scale = noise::tile_period(scale.extend(1.0)).truncate();
+            (scale, Some(scale.extend(f32::INFINITY)))
+        } else {
+            (scale, None)
+        };
+        Grid {
+            dimensions: (buffer_dimensions.0, buffer_dimensions.1, 1),
+            resolution: vec3(buffer_dimensions.0 as f32, buffer_dimensions.1 as f32, 1.0),
+            scale: scale.extend(1.0),
+            period,
+        }
+    }
+
+    fn new_3d(buffer_dimensions: (usize, usize, usize), scale: Vec3, tile: bool) -> Grid {
+        let (scale, period) = if tile {
+            let scale = noise::tile_period(scale);
+            (scale, Some(scale))
+        } else {
+            (scale, None)
+        };
+        Grid {
+            dimensions: buffer_dimensions,
+            resolution: vec3(
+                buffer_dimensions.0 as f32,
+                buffer_dimensions.1 as f32,
+                buffer_dimensions.2 as f32,
+            ),
+            scale,
+            period,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.dimensions.0 * self.dimensions.1 * self.dimensions.2
+    }
+
+    fn point(&self, i: usize) -> Vec3 {
+        let x = i % self.dimensions.0;
+        let y = i / self.dimensions.0 % self.dimensions.1;
+        let z = i / (self.dimensions.0 * self.dimensions.1);
+        vec3(x as f32, y as f32, z as f32) / self.resolution * self.scale
+    }
+
+    fn sample<T: Clone + Default + Send>(
+        &self,
+        sample: impl Fn(Vec3, Option<Vec3>) -> T + Sync,
+    ) -> Vec<T> {
+        par_fill(self.len(), |start, chunk| {
+            for (i, texel) in chunk.iter_mut().enumerate() {
+                *texel = sample(self.point(start + i), self.period);
+            }
+        })
+    }
+
+    fn sample_noise(&self, noise: &impl NoiseFn) -> Vec<f32> {
+        par_fill(self.len(), |start, chunk| {
+            let points: Vec<Vec3> = (start..start + chunk.len())
+                .map(|i| self.point(i))
+                .collect();
+            noise.sample_batch(&points, self.period, chunk);
+        })
+    }
+}
+
+fn sample_grid_2d<T: Clone + Default + Send>(
+    buffer_dimensions: (usize, usize),
+    scale: Vec2,
+    tile: bool,
+    sample: impl Fn(Vec3, Option<Vec3>) -> T + Sync,
+) -> Vec<T> {
+    Grid::new_2d(buffer_dimensions, scale, tile).sample(sample)
+}
+
+fn sample_grid_3d<T: Clone + Default + Send>(
+    buffer_dimensions: (usize, usize, usize),
+    scale: Vec3,
+    tile: bool,
+    sample: impl Fn(Vec3, Option<Vec3>) -> T + Sync,
+) -> Vec<T> {
+    Grid::new_3d(buffer_dimensions, scale, tile).sample(sample)
+}
+
+/// Native endian bytes for an `R32Float` image.
+pub fn r32_bytes(data: &[f32]) -> Vec<u8> {
+    data.iter().flat_map(|f| f.to_ne_bytes()).collect()
+}
+
+/// Native endian bytes for an `Rgba32Float` image.
+pub fn rgba32_bytes(data: &[Vec4]) -> Vec<u8> {
+    data.iter()
+        .flat_map(|v| v.to_array())
+        .flat_map(|f| f.to_ne_bytes())
+        .collect()
+}
+
+/// How baked values are stored in an image. The unorm encodings remap the values to
+/// [0, 1] over their range before quantising, see [`Encoded::range`].
+///
+/// `R16Unorm`/`Rgba16Unorm` need the `TEXTURE_FORMAT_16BIT_NORM` wgpu feature, see
+/// [`TexelEncoding::supported`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum TexelEncoding {
+    #[default]
+    Float32,
+    Unorm16,
+    Unorm8,
+}
+
+impl TexelEncoding {
+    pub fn format(self, channels: usize) -> TextureFormat {
+        return match (self, channels) {
+            (TexelEncoding::Float32, 1) => TextureFormat::R32Float,
+            (TexelEncoding::Unorm16, 1) => TextureFormat::R16Unorm,
+            (TexelEncoding::Unorm8, 1) => TextureFormat::R8Unorm,
+            (TexelEncoding::Float32, _) => TextureFormat::Rgba32Float,
+            (TexelEncoding::Unorm16, _) => TextureFormat::Rgba16Unorm,
+            (TexelEncoding::Unorm8, _) => TextureFormat::Rgba8Unorm,
+        };
+    }
+
+    /// This encoding if the device can sample it, otherwise `Float32`. Bevy requests
+    /// every feature the adapter has unless `WGPU_SETTINGS_PRIO` says otherwise, so
+    /// `Unorm16` only falls back on adapters without 16 bit norm formats.
+    pub fn supported(self, features: WgpuFeatures) -> TexelEncoding {
+        if self == TexelEncoding::Unorm16
+            && !features.contains(WgpuFeatures::TEXTURE_FORMAT_16BIT_NORM)
+        {
+            return TexelEncoding::Float32;
+        }
+        return self;
+    }
+}
+
+/// Image bytes for baked values and the range they were remapped from: a texel `t`
+/// stands for `range.x + t * (range.y - range.x)`. The range is (0, 1) for float
+/// encodings, so shaders can apply it unconditionally.
+pub struct Encoded {
+    pub bytes: Vec<u8>,
+    pub format: TextureFormat,
+    pub range: Vec2,
+}
+
+/// Smallest and largest value, widened to a unit range if they're equal so decoding
+/// doesn't divide by zero.
+fn value_range(values: impl Iterator<Item = f32>) -> Vec2 {
+    let (min, max) = values.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+        (min.min(v), max.max(v))
+    });
+    if min >= max {
+        let min = if min.is_finite() { min } else { 0.0 };
+        return vec2(min, min + 1.0);
+    }
+    return vec2(min, max);
+}
+
+fn encode_values(values: &[f32], channels: usize, encoding: TexelEncoding) -> Encoded {
+    let format = encoding.format(channels);
+    if encoding == TexelEncoding::Float32 {
+        return Encoded {
+            bytes: r32_bytes(values),
+            format,
+            range: vec2(0.0, 1.0),
+        };
+    }
+    let range = value_range(values.iter().copied());
+    let unorm =
+        |v: f32, levels: f32| ((v - range.x) / (range.y - range.x)).clamp(0.0, 1.0) * levels;
+    let bytes = match encoding {
+        TexelEncoding::Unorm16 => values
+            .iter()
+            .flat_map(|v| (unorm(*v, 65535.0).round() as u16).to_ne_bytes())
+            .collect(),
+        _ => values
+            .iter()
+            .map(|v| unorm(*v, 255.0).round() as u8)
+            .collect(),
+    };
+    return Encoded {
+        bytes,
+        format,
+        range,
+    };
+}
+
+/// Packs scalar baked values for an `R32Float`, `R16Unorm` or `R8Unorm` image.
+pub fn encode(data: &[f32], encoding: TexelEncoding) -> Encoded {
+    encode_values(data, 1, encoding)
+}
+
+/// Packs vector baked values for an `Rgba32Float`, `Rgba16Unorm` or `Rgba8Unorm`
+/// image, all four channels share one range.
+pub fn encode_rgba(data: &[Vec4], encoding: TexelEncoding) -> Encoded {
+    let values: Vec<f32> = data.iter().flat_map(|v| v.to_array()).collect();
+    encode_values(&values, 4, encoding)
+}
+
+/// Reads back what [`encode`] or [`encode_rgba`] packed, as `f32`s in the original range.
+pub fn decode(encoded: &Encoded) -> Vec<f32> {
+    let range = encoded.range;
+    let decode = |t: f32| range.x + t * (range.y - range.x);
+    return match encoded.format {
+        TextureFormat::R16Unorm | TextureFormat::Rgba16Unorm => encoded
+            .bytes
+            .chunks_exact(2)
+            .map(|b| decode(u16::from_ne_bytes([b[0], b[1]]) as f32 / 65535.0))
+            .collect(),
+        TextureFormat::R8Unorm | TextureFormat::Rgba8Unorm => encoded
+            .bytes
+            .iter()
+            .map(|b| decode(*b as f32 / 255.0))
+            .collect(),
+        _ => encoded
+            .bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_ne_bytes(b.try_into().unwrap()))
+            .collect(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Largest difference between the decoded texels and the float output, as a fraction
+    // of one quantisation step
+    fn max_error(values: &[f32], encoded: &Encoded, levels: f32) -> f32 {
+        let step = (encoded.range.y - encoded.range.x) / levels;
+        return decode(encoded)
+            .iter()
+            .zip(values)
+            .map(|(decoded, value)| (decoded - value).abs() / step)
+            .fold(0.0, f32::max);
+    }
+
+    // The texels one past the last row and column of a tiling texture, which a `Repeat`
+    // sampler reads as the first ones
+    fn past_the_edge(dimensions: (usize, usize), scale: Vec2, noise: &impl NoiseFn) -> Vec<f32> {
+        let mut grid = Grid::new_2d(dimensions, scale, true);
+        grid.dimensions = (dimensions.0 + 1, dimensions.1 + 1, 1);
+        return grid.sample_noise(noise);
+    }
+
+    #[test]
+    fn tiling_textures_wrap_at_their_edges() {
+        let seed = noise::NoiseSeed(9);
+        let (width, height) = (48, 32);
+        let scale = vec2(6.0, 4.0);
+        let noises: [(&str, &dyn NoiseFn); 4] = [
+            ("noised", &noise::Noised { seed }),
+            ("worley_noise", &noise::WorleyNoise { seed }),
+            ("wfbm", &noise::Wfbm { seed }),
+            ("value_fbm", &noise::ValueFbm { seed }),
+        ];
+        for (name, noise) in noises {
+            let baked = noise_texture_2d((width, height), scale, true, &noise);
+            let extended = past_the_edge((width, height), scale, &noise);
+            let row = width + 1;
+            // the edges match bit for bit, not just closely
+            let first_column = (0..height).map(|y| baked[y * width].to_bits());
+            let past_column = (0..height).map(|y| extended[y * row + width].to_bits());
+            assert!(first_column.eq(past_column), "{} has a seam along x", name);
+            let first_row = (0..width).map(|x| baked[x].to_bits());
+            let past_row = (0..width).map(|x| extended[height * row + x].to_bits());
+            assert!(first_row.eq(past_row), "{} has a seam along y", name);
+            let mut inside = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)));
+            assert!(
+                inside.all(|(x, y)| extended[y * row + x] == baked[y * width + x]),
+                "{} moved when the grid grew",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn unorm_quantises_within_half_a_step() {
+        let seed = noise::NoiseSeed(3);
+        let data = noise_texture_2d((64, 64), vec2(4.0, 4.0), true, &noise::Noised { seed });
+        let rgba = vector_texture_3d((16, 16, 16), Vec3::splat(2.0), true, |p, period| {
+            noise::curl_noise(p, period.unwrap_or(noise::NO_PERIOD), seed)
+        });
+        let rgba_values: Vec<f32> = rgba.iter().flat_map(|v| v.to_array()).collect();
+
+        let encodings = [
+            (TexelEncoding::Unorm16, 65535.0, 2),
+            (TexelEncoding::Unorm8, 255.0, 1),
+        ];
+        for (encoding, levels, texel_bytes) in encodings {
+            let scalar = encode(&data, encoding);
+            assert_eq!(scalar.bytes.len(), data.len() * texel_bytes);
+            let error = max_error(&data, &scalar, levels);
+            assert!(
+                error <= 0.5 + 1e-2,
+                "{:?} scalar off by {} steps",
+                encoding,
+                error
+            );
+
+            let vector = encode_rgba(&rgba, encoding);
+            let error = max_error(&rgba_values, &vector, levels);
+            assert!(
+                error <= 0.5 + 1e-2,
+                "{:?} rgba off by {} steps",
+                encoding,
+                error
+            );
+        }
+
+        let float = encode(&data, TexelEncoding::Float32);
+        assert_eq!(decode(&float), data);
+    }
+
+    #[test]
+    fn unorm16_falls_back_without_the_feature() {
+        let without = WgpuFeatures::empty();
+        let with = WgpuFeatures::TEXTURE_FORMAT_16BIT_NORM;
+        assert_eq!(
+            TexelEncoding::Unorm16.supported(without),
+            TexelEncoding::Float32
+        );
+        assert_eq!(
+            TexelEncoding::Unorm16.supported(with),
+            TexelEncoding::Unorm16
+        );
+        assert_eq!(
+            TexelEncoding::Unorm8.supported(without),
+            TexelEncoding::Unorm8
+        );
+        assert_eq!(
+            TexelEncoding::Float32.supported(without),
+            TexelEncoding::Float32
+        );
+    }
+}
@@ -0,0 +1,232 @@
+// offline bake of the procedural textures, so the app can load them instead of baking at
+// startup
+//
+//   cargo run --release --bin bake -- [--resolution N] [--seed N] [--format ktx2|exr|png] [--out DIR]
+//
+// Bakes every .proctex.ron file in the asset folder, the cloud blob volume and the fin
+// cloud texture. resolution overrides the size of the 2D proctex textures, the volumes
+// keep their sizes. The app only picks up ktx2 files written to assets/baked (the
+// default), and only while the seed and parameters stored in them match what it would
+// bake. exr and png are for looking at the textures in other tools.
+
+#![allow(clippy::needless_return)]
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    time::Instant,
+};
+
+use bevy::{
+    math::vec2,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use resume::{
+    bake, cloud_noise,
+    export::{self, TextureKind},
+    fin_texture,
+    mips::{self, MipFilter},
+    noise::NoiseSeed,
+    prebaked::{self, BakeKey},
+    proctex::{self, ProcTex},
+};
+
+#[derive(Clone, Copy)]
+enum Format {
+    Ktx2,
+    Exr,
+    Png,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        return match self {
+            Format::Ktx2 => "ktx2",
+            Format::Exr => "exr",
+            Format::Png => "png",
+        };
+    }
+
+    fn write(self, image: &Image, key: BakeKey, range: Vec2, path: &Path) -> io::Result<()> {
+        return match self {
+            Format::Ktx2 => prebaked::write(image, key, range, path),
+            Format::Exr => export::write_exr(image, path),
+            Format::Png => export::write_png16(image, path),
+        };
+    }
+}
+
+struct Args {
+    resolution: Option<u32>,
+    seed: NoiseSeed,
+    format: Format,
+    out: PathBuf,
+}
+
+fn usage(error: &str) -> ! {
+    eprintln!("{}", error);
+    eprintln!("usage: bake [--resolution N] [--seed N] [--format ktx2|exr|png] [--out DIR]");
+    process::exit(2);
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        resolution: None,
+        seed: NoiseSeed::default(),
+        format: Format::Ktx2,
+        out: assets_dir().join(prebaked::PREBAKED_DIR),
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        let Some(value) = iter.next() else {
+            usage(&format!("missing value for {}", flag));
+        };
+        match flag.as_str() {
+            "--resolution" => {
+                args.resolution = Some(
+                    value
+                        .parse()
+                        .unwrap_or_else(|_| usage(&format!("bad resolution {}", value))),
+                );
+            }
+            "--seed" => {
+                args.seed = NoiseSeed(
+                    value
+                        .parse()
+                        .unwrap_or_else(|_| usage(&format!("bad seed {}", value))),
+                );
+            }
+            "--format" => {
+                args.format = match value.as_str() {
+                    "ktx2" => Format::Ktx2,
+                    "exr" => Format::Exr,
+                    "png" => Format::Png,
+                    _ => usage(&format!("unknown format {}", value)),
+                };
+            }
+            "--out" => args.out = PathBuf::from(value),
+            _ => usage(&format!("unknown argument {}", flag)),
+        }
+    }
+    return args;
+}
+
+// where the app's AssetServer reads from
+fn assets_dir() -> PathBuf {
+    return bevy::asset::FileAssetIo::get_base_path().join("assets");
+}
+
+// every .proctex.ron file under `dir`, relative to the asset folder
+fn find_proctex(dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_proctex(&path, found)?;
+        } else if path.to_string_lossy().ends_with(".proctex.ron") {
+            found.push(path.strip_prefix(assets_dir()).unwrap().to_owned());
+        }
+    }
+    return Ok(());
+}
+
+// a .proctex.ron file with the noise graph or distance field it reads inlined
+fn read_proctex(path: &Path) -> Result<ProcTex, String> {
+    let read = |path: &Path| {
+        fs::read(assets_dir().join(path)).map_err(|e| format!("Error reading {:?}: {:?}", path, e))
+    };
+    let mut texture: ProcTex = ron::de::from_bytes(&read(path)?)
+        .map_err(|e| format!("Error parsing {:?}: {}", path, e))?;
+    if let Some(path) = texture.reads().map(str::to_string) {
+        let bytes = read(Path::new(&path))?;
+        texture
+            .inline_graph(&bytes)
+            .map_err(|e| format!("Error parsing {}: {}", path, e))?;
+        texture
+            .inline_sdf(&bytes)
+            .map_err(|e| format!("Error parsing {}: {}", path, e))?;
+    }
+    return Ok(texture);
+}
+
+fn exit_with(error: impl std::fmt::Display) -> ! {
+    eprintln!("{}", error);
+    process::exit(1);
+}
+
+// a texture and its decode range
+type Baker = Box<dyn Fn() -> (Image, Vec2)>;
+
+fn main() {
+    let args = parse_args();
+    let seed = args.seed;
+
+    let mut paths = Vec::new();
+    if let Err(e) = find_proctex(&assets_dir(), &mut paths) {
+        exit_with(format!("Error listing {:?}: {:?}", assets_dir(), e));
+    }
+    paths.sort();
+    let mut bakes: Vec<(String, BakeKey, Baker)> = Vec::new();
+    for path in paths {
+        let mut texture = read_proctex(&path).unwrap_or_else(|e| exit_with(e));
+        if let (Some(res), TextureKind::D2) = (args.resolution, texture.dimension) {
+            texture.resolution = (res, res, 1);
+        }
+        // float textures whatever the file says, the exporters only write float images
+        texture.format = bake::TexelEncoding::Float32;
+        let name = proctex::prebaked_name(&path);
+        let key = texture.bake_key(seed);
+        let bake = move || match texture.bake(seed) {
+            Ok(baked) => (baked.image, baked.range),
+            Err(e) => exit_with(format!("Error baking {:?}: {}", path, e)),
+        };
+        bakes.push((name, key, Box::new(bake)));
+    }
+
+    let params = cloud_noise::blob_params();
+    let blob = move || {
+        let res = cloud_noise::BLOB_RES;
+        let data = cloud_noise::blob_volume(res, seed);
+        let image = Image::new(
+            Extent3d {
+                width: res as u32,
+                height: res as u32,
+                depth_or_array_layers: res as u32,
+            },
+            TextureDimension::D3,
+            bake::r32_bytes(&data),
+            TextureFormat::R32Float,
+        );
+        (
+            mips::with_mips(image, MipFilter::Kaiser, false),
+            vec2(0.0, 1.0),
+        )
+    };
+    bakes.push((
+        "cloud_blob".into(),
+        BakeKey { seed, params },
+        Box::new(blob),
+    ));
+
+    let params = fin_texture::fin_params();
+    let fin = move || {
+        let mesh = fin_texture::base_mesh();
+        let image = fin_texture::fin_image(&mesh, fin_texture::FIN_RES, seed)
+            .unwrap_or_else(|| exit_with("Error baking fin_cloud: the base mesh has no UVs"));
+        (image, vec2(0.0, 1.0))
+    };
+    bakes.push(("fin_cloud".into(), BakeKey { seed, params }, Box::new(fin)));
+
+    for (name, key, bake) in bakes {
+        let start = Instant::now();
+        let (image, range) = bake();
+        let path = args.out.join(&name).with_extension(args.format.extension());
+        let written = fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| args.format.write(&image, key, range, &path));
+        if let Err(e) = written {
+            exit_with(format!("Error writing {:?}: {:?}", path, e));
+        }
+        println!("Baked {:?} in {:.1?}", path, start.elapsed());
+    }
+}
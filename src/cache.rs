@@ -0,0 +1,368 @@
+//! On-disk cache for baked texture data.
+//!
+//! A cache file is a fixed size header followed by the texels, optionally zlib
+//! compressed. The header records everything the data depends on (dimensions, texel
+//! format, seed and a hash of the generator parameters), so [`load_or_bake`] notices a
+//! stale file and bakes it again instead of loading data of the wrong shape.
+//!
+//! Header fields are always little-endian. Texels are written in the host byte order,
+//! which the header records, and swapped on load if needed.
+
+use std::{
+    fs,
+    hash::{Hash, Hasher},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use bevy::log::{info, warn};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::noise::NoiseSeed;
+
+pub const MAGIC: [u8; 4] = *b"NOIZ";
+/// Bumped whenever the layout below changes.
+pub const VERSION: u16 = 1;
+pub const HEADER_SIZE: usize = 36;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TexelFormat {
+    R32Float = 0,
+    Rgba32Float = 1,
+}
+
+impl TexelFormat {
+    pub fn channels(self) -> usize {
+        return match self {
+            TexelFormat::R32Float => 1,
+            TexelFormat::Rgba32Float => 4,
+        };
+    }
+
+    fn from_u8(v: u8) -> Option<Self> {
+        return match v {
+            0 => Some(TexelFormat::R32Float),
+            1 => Some(TexelFormat::Rgba32Float),
+            _ => None,
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Endianness {
+    Little = 0,
+    Big = 1,
+}
+
+impl Endianness {
+    pub const NATIVE: Endianness = if cfg!(target_endian = "big") {
+        Endianness::Big
+    } else {
+        Endianness::Little
+    };
+}
+
+/// What a cache file holds and what it was generated from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheHeader {
+    pub dimensions: (u32, u32, u32),
+    pub format: TexelFormat,
+    pub seed: NoiseSeed,
+    /// See [`param_hash`]
+    pub params: u64,
+}
+
+impl CacheHeader {
+    pub fn new(
+        dimensions: (usize, usize, usize),
+        format: TexelFormat,
+        seed: NoiseSeed,
+        params: u64,
+    ) -> Self {
+        return Self {
+            dimensions: (
+                dimensions.0 as u32,
+                dimensions.1 as u32,
+                dimensions.2 as u32,
+            ),
+            format,
+            seed,
+            params,
+        };
+    }
+
+    /// Number of f32 values in the data.
+    pub fn len(&self) -> usize {
+        let (w, h, d) = self.dimensions;
+        return w as usize * h as usize * d as usize * self.format.channels();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    fn to_bytes(self, endianness: Endianness, compressed: bool) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[6] = self.format as u8;
+        bytes[7] = endianness as u8;
+        bytes[8] = compressed as u8;
+        // 9..12 reserved
+        bytes[12..16].copy_from_slice(&self.dimensions.0.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.dimensions.1.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.dimensions.2.to_le_bytes());
+        bytes[24..28].copy_from_slice(&self.seed.0.to_le_bytes());
+        bytes[28..36].copy_from_slice(&self.params.to_le_bytes());
+        return bytes;
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<(Self, Endianness, bool)> {
+        if bytes.len() < HEADER_SIZE || bytes[0..4] != MAGIC {
+            return Err(invalid("not a noise cache file"));
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != VERSION {
+            return Err(invalid("unsupported cache version"));
+        }
+        let format =
+            TexelFormat::from_u8(bytes[6]).ok_or_else(|| invalid("unknown texel format"))?;
+        let endianness = match bytes[7] {
+            0 => Endianness::Little,
+            1 => Endianness::Big,
+            _ => return Err(invalid("unknown endianness")),
+        };
+        let header = CacheHeader {
+            dimensions: (u32_at(12), u32_at(16), u32_at(20)),
+            format,
+            seed: NoiseSeed(u32_at(24)),
+            params: u64::from_le_bytes(bytes[28..36].try_into().unwrap()),
+        };
+        return Ok((header, endianness, bytes[8] != 0));
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// 64 bit FNV-1a, stable across runs and platforms unlike `DefaultHasher`.
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ *b as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Hash of the generator parameters to store in a [`CacheHeader`]. Floats don't
+/// implement `Hash`, pass their `to_bits()`.
+pub fn param_hash(params: &impl Hash) -> u64 {
+    let mut hasher = Fnv1a::default();
+    params.hash(&mut hasher);
+    return hasher.finish();
+}
+
+/// Writes `data` with `header`, zlib compressed if `compress` is set.
+pub fn write_cache(
+    path: impl AsRef<Path>,
+    header: &CacheHeader,
+    data: &[f32],
+    compress: bool,
+) -> io::Result<()> {
+    assert_eq!(data.len(), header.len());
+    let mut bytes = header.to_bytes(Endianness::NATIVE, compress).to_vec();
+    let texels: Vec<u8> = data.iter().flat_map(|f| f.to_ne_bytes()).collect();
+    if compress {
+        let mut encoder = ZlibEncoder::new(bytes, Compression::default());
+        encoder.write_all(&texels)?;
+        bytes = encoder.finish()?;
+    } else {
+        bytes.extend(texels);
+    }
+    return fs::write(path, bytes);
+}
+
+/// Reads a cache file, returning its header and the texels in host byte order.
+pub fn read_cache(path: impl AsRef<Path>) -> io::Result<(CacheHeader, Vec<f32>)> {
+    let bytes = fs::read(path)?;
+    let (header, endianness, compressed) = CacheHeader::from_bytes(&bytes)?;
+    let texels = if compressed {
+        let mut texels = Vec::with_capacity(header.len() * 4);
+        ZlibDecoder::new(&bytes[HEADER_SIZE..]).read_to_end(&mut texels)?;
+        texels
+    } else {
+        bytes[HEADER_SIZE..].to_vec()
+    };
+    if texels.len() != header.len() * 4 {
+        return Err(invalid("cache data doesn't match its header"));
+    }
+    let data = texels
+        .chunks_exact(4)
+        .map(|b| {
+            let b = b.try_into().unwrap();
+            match endianness {
+                Endianness::Little => f32::from_le_bytes(b),
+                Endianness::Big => f32::from_be_bytes(b),
+            }
+        })
+        .collect();
+    return Ok((header, data));
+}
+
+/// Loads the data at `path` if its header matches `header`, otherwise runs `bake` and
+/// writes the result back for next time.
+pub fn load_or_bake(
+    path: impl AsRef<Path>,
+    header: &CacheHeader,
+    compress: bool,
+    bake: impl FnOnce() -> Vec<f32>,
+) -> Vec<f32> {
+    let path = path.as_ref();
+    match read_cache(path) {
+        Ok((cached, data)) if cached == *header => return data,
+        Ok(_) => info!("Noise cache {:?} is stale, baking it again", path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Error reading noise cache {:?}: {:?}", path, e),
+    }
+    let data = bake();
+    if let Err(e) = write_cache(path, header, &data, compress) {
+        warn!("Error writing noise cache {:?}: {:?}", path, e);
+    }
+    return data;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, path::PathBuf};
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "resume-cache-{}-{}.noise",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        return path;
+    }
+
+    fn header() -> CacheHeader {
+        CacheHeader::new(
+            (3, 2, 2),
+            TexelFormat::Rgba32Float,
+            NoiseSeed(7),
+            0xdead_beef,
+        )
+    }
+
+    fn texels(header: &CacheHeader) -> Vec<f32> {
+        (0..header.len()).map(|i| i as f32 * 0.37 - 1.5).collect()
+    }
+
+    #[test]
+    fn header_round_trips() {
+        for endianness in [Endianness::Little, Endianness::Big] {
+            for compressed in [false, true] {
+                let bytes = header().to_bytes(endianness, compressed);
+                let read = CacheHeader::from_bytes(&bytes).unwrap();
+                assert_eq!(read, (header(), endianness, compressed));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let mut bytes = header().to_bytes(Endianness::Little, false);
+        bytes[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(CacheHeader::from_bytes(&bytes).is_err());
+        bytes[0] = b'X';
+        assert!(CacheHeader::from_bytes(&bytes).is_err());
+        assert!(CacheHeader::from_bytes(&bytes[..HEADER_SIZE - 1]).is_err());
+    }
+
+    #[test]
+    fn data_round_trips() {
+        for compress in [false, true] {
+            let path = temp_path(&format!("round-trip-{}", compress));
+            let data = texels(&header());
+            write_cache(&path, &header(), &data, compress).unwrap();
+            assert_eq!(read_cache(&path).unwrap(), (header(), data));
+            fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn swaps_foreign_byte_order() {
+        let path = temp_path("foreign");
+        let data = texels(&header());
+        let foreign = match Endianness::NATIVE {
+            Endianness::Little => Endianness::Big,
+            Endianness::Big => Endianness::Little,
+        };
+        let mut bytes = header().to_bytes(foreign, false).to_vec();
+        bytes.extend(
+            data.iter()
+                .flat_map(|f| f.to_bits().swap_bytes().to_ne_bytes()),
+        );
+        fs::write(&path, bytes).unwrap();
+        assert_eq!(read_cache(&path).unwrap().1, data);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bakes_only_when_stale() {
+        let path = temp_path("stale");
+        let bakes = Cell::new(0);
+        let load = |header: &CacheHeader| {
+            load_or_bake(&path, header, true, || {
+                bakes.set(bakes.get() + 1);
+                texels(header)
+            })
+        };
+
+        assert_eq!(load(&header()), texels(&header()));
+        assert_eq!(bakes.get(), 1);
+        assert_eq!(load(&header()), texels(&header()));
+        assert_eq!(bakes.get(), 1, "a matching cache file is loaded, not baked");
+
+        let reseeded = CacheHeader {
+            seed: NoiseSeed(8),
+            ..header()
+        };
+        let resized = CacheHeader {
+            dimensions: (4, 2, 2),
+            ..header()
+        };
+        let edited = CacheHeader {
+            params: 1,
+            ..header()
+        };
+        for (i, stale) in [reseeded, resized, edited].iter().enumerate() {
+            assert_eq!(load(stale), texels(stale));
+            assert_eq!(bakes.get(), 2 + i, "{:?} is stale", stale);
+        }
+
+        // unreadable files are baked again too
+        fs::write(&path, b"NOIZ garbage").unwrap();
+        assert_eq!(load(&header()), texels(&header()));
+        assert_eq!(bakes.get(), 5);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,13 +1,17 @@
-use bevy::math::vec2;
-
 // use crate::noise::fbmd;
-use crate::{noise, CameraController};
+use crate::CameraController;
 use bevy::{
     math::vec3,
     prelude::*,
     reflect::TypeUuid,
     render::render_resource::{AsBindGroup, Extent3d, ShaderRef, TextureDimension, TextureFormat},
 };
+use resume::{
+    async_bake::PendingBakes,
+    bake,
+    noise::{self, FractalSettings, NoiseSeed},
+    proctex::{self, LoadedProcTex},
+};
 
 #[derive(Component, Default, Reflect)]
 pub struct RMCloud {
@@ -20,18 +24,52 @@ pub struct RMCloud {
     pub cloud_coef: f32,
     pub cloud_height: f32,
     pub scroll: f32,
+    pub flow_strength: f32,
+    pub value_warp: f32,
+    pub anim_fps: f32,
+    pub anim_mix: f32,
+    pub shape_factor: f32,
+    pub shadow_jitter: f32,
+    pub sdf_factor: f32,
+}
+
+/// Octaves of the worley and value textures. Editing them re-bakes the textures with the
+/// first fractal of their graph swapped for these, editing a file or NoiseSeed reloads it
+/// as written.
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource)]
+pub struct CloudNoise {
+    pub worley: FractalSettings,
+    pub value: FractalSettings,
+}
+
+impl Default for CloudNoise {
+    fn default() -> Self {
+        Self {
+            worley: FractalSettings::wfbm(),
+            value: FractalSettings::value_fbm(),
+        }
+    }
 }
 
 pub struct RMCloudPlugin;
 impl Plugin for RMCloudPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<RMCloud>();
+        app.register_type::<NoiseSeed>();
+        app.register_type::<CloudNoise>();
+        app.register_type::<FractalSettings>();
+        app.register_type::<noise::FractalKind>();
+        app.init_resource::<NoiseSeed>();
+        app.init_resource::<CloudNoise>();
         app.add_plugin(MaterialPlugin::<RMCloudMaterial>::default());
         app.add_system(
             |cam: Query<&Transform, With<CameraController>>,
              clouds: Query<(&RMCloud, &Transform)>,
              sun: Query<&Transform, With<DirectionalLight>>,
              mut cloud_materials: ResMut<Assets<RMCloudMaterial>>,
+             pending: Res<PendingBakes>,
+             images: Res<Assets<Image>>,
              time: Res<Time>| {
                 let camera_position = cam.get_single().unwrap().translation;
                 let sun_dir = sun.get_single().unwrap().forward();
@@ -40,6 +78,21 @@ impl Plugin for RMCloudPlugin {
                         material.camera_position = camera_position;
                         material.time = time.raw_elapsed_seconds();
                         material.sun_direction = sun_dir;
+                        material.frame = material.frame.wrapping_add(1);
+                        // follows the encoding of the last (re)load
+                        if let Some(worley) = &material.worley {
+                            material.worley_range = pending.range(worley);
+                        }
+                        if let Some(value) = &material.value {
+                            material.value_range = pending.range(value);
+                        }
+                        // one frame per layer, however many the animation was baked with
+                        if let Some(animated) =
+                            material.animated.as_ref().and_then(|a| images.get(a))
+                        {
+                            material.frame_count =
+                                animated.texture_descriptor.size.depth_or_array_layers;
+                        }
                     }
                 }
             },
@@ -48,73 +101,114 @@ impl Plugin for RMCloudPlugin {
         app.add_system(
             |mut clouds: Query<&mut RMCloud>, mut materials: ResMut<Assets<RMCloudMaterial>>| {
                 for cloud in clouds.iter_mut() {
-                    match materials.get_mut(&cloud.handle) {
-                        Some(material) => {
-                            material.shadow_dist = cloud.shadow_dist;
-                            material.shadow_coef = cloud.shadow_coef;
-                            material.worley_factor = cloud.worley_factor;
-                            material.value_factor = cloud.value_factor;
-                            material.cloud_coef = cloud.cloud_coef;
-                            material.cloud_height = cloud.cloud_height;
-                            material.sun_pen = cloud.sun_pen;
-                            material.scroll = cloud.scroll;
-                        }
-                        None => {}
+                    if let Some(material) = materials.get_mut(&cloud.handle) {
+                        material.shadow_dist = cloud.shadow_dist;
+                        material.shadow_coef = cloud.shadow_coef;
+                        material.worley_factor = cloud.worley_factor;
+                        material.value_factor = cloud.value_factor;
+                        material.cloud_coef = cloud.cloud_coef;
+                        material.cloud_height = cloud.cloud_height;
+                        material.sun_pen = cloud.sun_pen;
+                        material.scroll = cloud.scroll;
+                        material.flow_strength = cloud.flow_strength;
+                        material.value_warp = cloud.value_warp;
+                        material.anim_fps = cloud.anim_fps;
+                        material.anim_mix = cloud.anim_mix;
+                        material.shape_factor = cloud.shape_factor;
+                        material.shadow_jitter = cloud.shadow_jitter;
+                        material.sdf_factor = cloud.sdf_factor;
+                    }
+                }
+            },
+        );
+
+        app.add_system(
+            |cloud_noise: Res<CloudNoise>,
+             seed: Res<NoiseSeed>,
+             clouds: Query<&RMCloud>,
+             cloud_materials: Res<Assets<RMCloudMaterial>>,
+             textures: Res<Assets<LoadedProcTex>>,
+             asset_server: Res<AssetServer>,
+             mut pending: ResMut<PendingBakes>| {
+                // the files already bake the defaults
+                if !cloud_noise.is_changed() || cloud_noise.is_added() {
+                    return;
+                }
+                for cloud in &clouds {
+                    let Some(material) = cloud_materials.get(&cloud.handle) else {
+                        continue;
                     };
+                    let tuned = [
+                        (&material.worley, cloud_noise.worley),
+                        (&material.value, cloud_noise.value),
+                    ];
+                    for (image, settings) in tuned {
+                        let Some(image) = image else {
+                            continue;
+                        };
+                        let Some(loaded) = proctex::loaded_from(&asset_server, &textures, image)
+                        else {
+                            continue;
+                        };
+                        let mut texture = loaded.texture.clone();
+                        let Some(fractal) = texture.fractal_mut() else {
+                            continue;
+                        };
+                        *fractal = settings;
+                        let seed = *seed;
+                        pending.try_replace(image.clone(), move || texture.bake(seed));
+                    }
                 }
             },
         );
 
         app.add_startup_system(
             |mut commands: Commands,
-             // asset_server: Res<AssetServer>,
+             asset_server: Res<AssetServer>,
              mut meshes: ResMut<Assets<Mesh>>,
              // mut materials: ResMut<Assets<StandardMaterial>>,
              mut cloud_materials: ResMut<Assets<RMCloudMaterial>>,
              // mut noise_materials: ResMut<Assets<NoiseMaterial>>,
-             mut images: ResMut<Assets<Image>>| {
-                let res = (1000, 1000);
-                let re3 = 2;
+             mut images: ResMut<Assets<Image>>,
+             pending: Res<PendingBakes>,
+             seed: Res<NoiseSeed>| {
+                let seed = *seed;
+                // baked from their .proctex.ron files unless the bake binary prebaked them,
+                // a placeholder while loading and the full texture on the async pool, and
+                // again whenever a file, the noise graph it reads or NoiseSeed is edited
+                let shape = asset_server.load("clouds/shape.proctex.ron");
+                let detail = asset_server.load("clouds/detail.proctex.ron");
+                let flow = asset_server.load("clouds/flow.proctex.ron");
+                let animated = asset_server.load("clouds/animated.proctex.ron");
+                let worley: Handle<Image> = asset_server.load("clouds/worley.proctex.ron");
+                let value: Handle<Image> = asset_server.load("clouds/value.proctex.ron");
+                let sdf = asset_server.load("clouds/sdf.proctex.ron");
 
-                let w3d = {
-                    images.add(Image::new(
-                        Extent3d {
-                            width: re3 as u32,
-                            height: re3 as u32,
-                            depth_or_array_layers: re3 as u32,
-                        },
-                        TextureDimension::D3,
-                        w3noise(re3)
-                            .iter()
-                            .flat_map(|f| f.to_ne_bytes())
-                            .collect::<Vec<u8>>(),
-                        TextureFormat::R32Float,
-                    ))
-                };
-
-                let mut make_image = |data: &[f32]| {
-                    images.add(Image::new(
-                        Extent3d {
-                            width: res.0 as u32,
-                            height: res.1 as u32,
-                            depth_or_array_layers: 1,
-                        },
-                        TextureDimension::D2,
-                        data.iter()
-                            .flat_map(|f| f.to_ne_bytes())
-                            .collect::<Vec<u8>>(),
-                        TextureFormat::R32Float,
-                    ))
-                };
+                // jitters the shadow march so the steps don't band, read texel by texel so
+                // it has no mips
+                let blue_res = 64;
+                let blue_noise = images.add(Image::new(
+                    Extent3d {
+                        width: blue_res as u32,
+                        height: blue_res as u32,
+                        depth_or_array_layers: 1,
+                    },
+                    TextureDimension::D2,
+                    bake::r32_bytes(&noise::blue_noise_2d((blue_res, blue_res), seed)),
+                    TextureFormat::R32Float,
+                ));
 
-                let wnoise = worley_texture_data(res, vec2(5., 5.));
-                let vnoise = value_texture_data(res, vec2(5., 5.));
-                let worley = make_image(&wnoise);
-                let value = make_image(&vnoise);
                 let material = cloud_materials.add(RMCloudMaterial {
                     worley: Some(worley.clone()),
                     value: Some(value.clone()),
-                    w3d: Some(w3d),
+                    shape: Some(shape),
+                    detail: Some(detail),
+                    blue_noise: Some(blue_noise),
+                    flow: Some(flow),
+                    worley_range: pending.range(&worley),
+                    value_range: pending.range(&value),
+                    animated: Some(animated),
+                    sdf: Some(sdf),
                     sun_direction: vec3(1., 1., 0.).normalize(),
                     ..default()
                 });
@@ -129,6 +223,11 @@ impl Plugin for RMCloudPlugin {
                         value_factor: 0.0,
                         cloud_coef: 0.2,
                         cloud_height: 0.2,
+                        flow_strength: 0.05,
+                        anim_fps: 2.0,
+                        anim_mix: 0.3,
+                        shadow_jitter: 1.0,
+                        sdf_factor: 0.5,
                         ..Default::default()
                     },
                     MaterialMeshBundle {
@@ -150,45 +249,10 @@ impl Plugin for RMCloudPlugin {
     }
 }
 
-fn w3noise(res: usize) -> Vec<f32> {
-    let scale = vec3(10., 10., 10.);
-    let resolution = vec3(res as f32, res as f32, res as f32);
-    (0..res)
-        .flat_map(|x| (0..res).flat_map(move |y| (0..res).map(move |z| (x, y, z))))
-        .map(|(x, y, z)| {
-            let p = vec3(x as f32, y as f32, z as f32) / resolution * scale;
-            noise::wfbm(p, scale)
-        })
-        .collect()
-}
-
 // A 16^3 chunk with 1-voxel boundary padding.
 
 // This chunk will cover just a single octant of a sphere SDF (radius 15).
 
-pub fn worley_texture_data(buffer_dimensions: (usize, usize), scale: Vec2) -> Vec<f32> {
-    let resolution = vec2(buffer_dimensions.0 as f32, buffer_dimensions.1 as f32);
-    (0..buffer_dimensions.0)
-        .flat_map(move |x| (0..buffer_dimensions.1).map(move |y| (x, y)))
-        .map(|(x, y)| {
-            let p = vec2(x.as_f32(), y.as_f32()) / resolution * scale;
-            noise::wfbm(p.extend(0.0), vec3(scale.x, scale.y, scale.y))
-        })
-        .collect()
-}
-
-pub fn value_texture_data(buffer_dimensions: (usize, usize), scale: Vec2) -> Vec<f32> {
-    let resolution = vec2(buffer_dimensions.0 as f32, buffer_dimensions.1 as f32);
-    (0..buffer_dimensions.0)
-        .flat_map(|x| (0..buffer_dimensions.1).map(move |y| (x, y)))
-        .map(|(x, y)| {
-            let p = vec2(x.as_f32(), y.as_f32()) / resolution * scale;
-            // let d = cloud_sdf(p);
-            noise::value_fbm(p.extend(0.0), vec3(scale.x, scale.y, scale.y))
-        })
-        .collect()
-}
-
 // pub fn new_cloud_data(buffer_dimensions: [usize; 3]) -> Vec<Vec4> {
 //     let resolution = vec3(
 //         buffer_dimensions[0] as f32,
@@ -293,30 +357,6 @@ pub fn value_texture_data(buffer_dimensions: (usize, usize), scale: Vec2) -> Vec
 //         .collect_vec()
 // }
 
-#[allow(dead_code)]
-fn rotate(v: Vec3, x: f32, y: f32, z: f32) -> Vec3 {
-    Mat3::from_euler(bevy::prelude::EulerRot::XYZ, x, y, z) * v
-}
-
-pub trait AsF32 {
-    fn as_f32(self) -> f32;
-}
-impl AsF32 for f32 {
-    fn as_f32(self) -> f32 {
-        self
-    }
-}
-impl AsF32 for u32 {
-    fn as_f32(self) -> f32 {
-        self as f32
-    }
-}
-impl AsF32 for usize {
-    fn as_f32(self) -> f32 {
-        self as f32
-    }
-}
-
 /// The Material trait is very configurable, but comes with sensible defaults for all methods.
 /// You only need to implement functions for features that need non-default behavior. See the Material api docs for details!
 impl Material for RMCloudMaterial {
@@ -355,6 +395,29 @@ pub struct RMCloudMaterial {
     pub cloud_height: f32,
     #[uniform(0)]
     pub scroll: f32,
+    #[uniform(0)]
+    pub flow_strength: f32,
+    #[uniform(0)]
+    pub value_warp: f32,
+    #[uniform(0)]
+    pub frame_count: u32,
+    #[uniform(0)]
+    pub anim_fps: f32,
+    #[uniform(0)]
+    pub anim_mix: f32,
+    #[uniform(0)]
+    pub shape_factor: f32,
+    #[uniform(0)]
+    pub frame: u32,
+    #[uniform(0)]
+    pub shadow_jitter: f32,
+    #[uniform(0)]
+    pub sdf_factor: f32,
+    /// What the worley and value texels decode to, see [`bake::Encoded::range`]
+    #[uniform(0)]
+    pub worley_range: Vec2,
+    #[uniform(0)]
+    pub value_range: Vec2,
 
     #[texture(1)]
     #[sampler(2)]
@@ -364,5 +427,83 @@ pub struct RMCloudMaterial {
     pub value: Option<Handle<Image>>,
     #[texture(5, dimension = "3d")]
     #[sampler(6)]
-    pub w3d: Option<Handle<Image>>,
+    pub shape: Option<Handle<Image>>,
+    #[texture(7, dimension = "3d")]
+    #[sampler(8)]
+    pub flow: Option<Handle<Image>>,
+    #[texture(9, dimension = "2d_array")]
+    #[sampler(10)]
+    pub animated: Option<Handle<Image>>,
+    #[texture(11, dimension = "3d")]
+    #[sampler(12)]
+    pub detail: Option<Handle<Image>>,
+    #[texture(13)]
+    pub blue_noise: Option<Handle<Image>>,
+    /// Distances to the cloud shape over the plane, see [`resume::sdf`]
+    #[texture(14, dimension = "3d")]
+    #[sampler(15)]
+    pub sdf: Option<Handle<Image>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use naga::{
+        valid::{Capabilities, ValidationFlags, Validator},
+        Expression, SampleLevel,
+    };
+
+    fn asset(path: &str) -> String {
+        return std::fs::read_to_string(std::path::Path::new("assets").join(path)).unwrap();
+    }
+
+    // cloud.wgsl with bevy's imports spliced in the way its preprocessor would, the mesh
+    // has uvs
+    fn cloud_source() -> String {
+        let vertex_output = "@location(0) world_position: vec4<f32>,
+            @location(1) world_normal: vec3<f32>,
+            @location(2) uv: vec2<f32>,";
+        let mut source = asset("shaders/cloud.wgsl")
+            .replace("#import bevy_pbr::mesh_vertex_output", vertex_output);
+        for import in ["noise_warp", "blue_noise"] {
+            let module = asset(&format!("shaders/{}.wgsl", import))
+                .replace(&format!("#define_import_path portfolio::{}", import), "");
+            source = source.replace(&format!("#import portfolio::{}", import), &module);
+        }
+        return source;
+    }
+
+    #[test]
+    fn cloud_shader_validates() {
+        let source = cloud_source();
+        let module = naga::front::wgsl::parse_str(&source)
+            .unwrap_or_else(|e| panic!("{}", e.emit_to_string(&source)));
+        if let Err(e) =
+            Validator::new(ValidationFlags::all(), Capabilities::empty()).validate(&module)
+        {
+            panic!("cloud.wgsl doesn't validate: {:?}", e);
+        }
+    }
+
+    // naga's uniformity analysis covers the entry point but doesn't follow calls, and the
+    // cloud is sampled in a loop that runs a different number of times per pixel
+    #[test]
+    fn cloud_functions_sample_with_explicit_gradients() {
+        let module = naga::front::wgsl::parse_str(&cloud_source()).unwrap();
+        for (_, function) in module.functions.iter() {
+            let implicit = function.expressions.iter().any(|(_, e)| {
+                matches!(
+                    e,
+                    Expression::ImageSample {
+                        level: SampleLevel::Auto | SampleLevel::Bias(_),
+                        ..
+                    }
+                )
+            });
+            assert!(
+                !implicit,
+                "{:?} samples with implicit derivatives",
+                function.name
+            );
+        }
+    }
 }
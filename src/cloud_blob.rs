@@ -2,29 +2,46 @@ use bevy::{
     math::{vec2, vec3},
     prelude::*,
     reflect::TypeUuid,
-    render::render_resource::{AsBindGroup, ShaderRef, TextureFormat},
+    render::{
+        render_resource::{AsBindGroup, ShaderRef},
+        renderer::RenderDevice,
+    },
 };
+use futures_lite::future;
 use rand::prelude::*;
 use std::ops::{Add, Mul, Sub};
 
-use crate::{noise, CameraController};
+use resume::{
+    async_bake::{Baked, PendingBakes},
+    bake, cache,
+    cloud_noise::{self, BLOB_RES},
+    mips::{self, MipFilter},
+    noise::NoiseSeed,
+    prebaked,
+};
+
+use crate::CameraController;
 
 #[derive(Component, Default)]
 struct CloudBlob {
     handle: Handle<CloudBlobMaterial>,
 }
 
+// commented out in main.rs
+#[allow(dead_code)]
 pub struct CloudBlobPlugin;
 
 #[allow(dead_code)]
 impl Plugin for CloudBlobPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(MaterialPlugin::<CloudBlobMaterial>::default());
+        app.init_resource::<NoiseSeed>();
         app.add_system(
             |camera: Query<&Transform, With<CameraController>>,
              sun: Query<&Transform, With<DirectionalLight>>,
              clouds: Query<(&CloudBlob, &Transform)>,
              mut materials: ResMut<Assets<CloudBlobMaterial>>,
+             pending: Res<PendingBakes>,
              time: Res<Time>| {
                 let camera_position = camera.get_single().unwrap().translation;
                 let sun_facing = sun.get_single().unwrap().forward();
@@ -34,6 +51,9 @@ impl Plugin for CloudBlobPlugin {
                         material.time = time.raw_elapsed_seconds();
                         material.scale = transform.scale;
                         material.sun_direction = sun_facing;
+                        if let Some(noise) = &material.noise {
+                            material.noise_range = pending.range(noise);
+                        }
                     }
                 }
             },
@@ -41,56 +61,61 @@ impl Plugin for CloudBlobPlugin {
 
         const SECTORS: usize = 10;
         const STACKS: usize = 10;
-        const TEXTURE_RES: usize = 200;
         app.add_startup_system(
             |mut materials: ResMut<Assets<CloudBlobMaterial>>,
              mut commands: Commands,
+             asset_server: Res<AssetServer>,
              mut meshes: ResMut<Assets<Mesh>>,
-             mut images: ResMut<Assets<Image>>| {
-                let path = "assets/noise_data";
-                let data = match std::fs::read(path) {
-                    Ok(data) => data,
-                    Err(_) => {
-                        let mut data = Box::new([[[0.0; TEXTURE_RES]; TEXTURE_RES]; TEXTURE_RES]);
-                        for x in 0..TEXTURE_RES {
-                            for y in 0..TEXTURE_RES {
-                                for z in 0..TEXTURE_RES {
-                                    let sample_pos = vec3(
-                                        x as f32 / TEXTURE_RES as f32,
-                                        y as f32 / TEXTURE_RES as f32,
-                                        z as f32 / TEXTURE_RES as f32,
-                                    ) * 10.;
-                                    data[x][y][z] = mix(
-                                        noise::fbmd(sample_pos).x,
-                                        noise::wfbm(sample_pos * 0.5, Vec3::ONE * 100.),
-                                        0.7,
-                                    )
-                                }
-                            }
-                        }
-                        let data = data
-                            .iter()
-                            .flatten()
-                            .flatten()
-                            .flat_map(|f| f.to_ne_bytes())
-                            .collect();
-                        if let Err(e) = std::fs::write(path, &data) {
-                            println!("Error writing noise data {:?}", e);
-                        }
-                        data
-                    }
+             mut images: ResMut<Assets<Image>>,
+             mut pending: ResMut<PendingBakes>,
+             seed: Res<NoiseSeed>,
+             render_device: Res<RenderDevice>| {
+                let seed = *seed;
+                // 16 bits per texel instead of 32 where the device can sample them, decoded
+                // with noise_range in the shader
+                let encoding = bake::TexelEncoding::Unorm16.supported(render_device.features());
+                let volume = move |res: usize, data: Vec<f32>| {
+                    let mut baked = Baked::new(
+                        bevy::render::render_resource::Extent3d {
+                            width: res as u32,
+                            height: res as u32,
+                            depth_or_array_layers: res as u32,
+                        },
+                        bevy::render::render_resource::TextureDimension::D3,
+                        bake::encode(&data, encoding),
+                    );
+                    mips::generate_mips(&mut baked.image, MipFilter::Kaiser, false);
+                    baked
                 };
+                let bake_volume = move |res: usize| cloud_noise::blob_volume(res, seed);
 
-                let texture = images.add(Image::new(
-                    bevy::render::render_resource::Extent3d {
-                        width: TEXTURE_RES as u32,
-                        height: TEXTURE_RES as u32,
-                        depth_or_array_layers: TEXTURE_RES as u32,
-                    },
-                    bevy::render::render_resource::TextureDimension::D3,
-                    data,
-                    TextureFormat::R32Float,
-                ));
+                // prebaked by the bake binary, otherwise a coarse volume until the full one
+                // is loaded from the cache or baked
+                let params = cloud_noise::blob_params();
+                let key = prebaked::BakeKey { seed, params };
+                let prebaked =
+                    future::block_on(prebaked::load(asset_server.asset_io(), "cloud_blob", key));
+                let placeholder_res = BLOB_RES / 8;
+                let texture = match prebaked {
+                    Some((image, _)) => images.add(image),
+                    None => pending.add(
+                        &mut images,
+                        volume(placeholder_res, bake_volume(placeholder_res)),
+                        move || {
+                            let header = cache::CacheHeader::new(
+                                (BLOB_RES, BLOB_RES, BLOB_RES),
+                                cache::TexelFormat::R32Float,
+                                seed,
+                                params,
+                            );
+                            let data =
+                                cache::load_or_bake("assets/noise_data", &header, true, || {
+                                    bake_volume(BLOB_RES)
+                                });
+                            volume(BLOB_RES, data)
+                        },
+                    ),
+                };
                 let mesh = meshes.add(
                     shape::UVSphere {
                         radius: 1.0,
@@ -99,7 +124,7 @@ impl Plugin for CloudBlobPlugin {
                     }
                     .into(),
                 );
-                let mut rng = thread_rng();
+                let mut rng = StdRng::seed_from_u64(seed.0 as u64);
                 for _ in 0..200 {
                     let xz =
                         vec2(rng.gen(), rng.gen()).add(vec2(-0.5, -0.5)) * vec2(10000., 10000.);
@@ -182,10 +207,6 @@ impl Plugin for CloudBlobPlugin {
     }
 }
 
-fn mix(a: f32, b: f32, t: f32) -> f32 {
-    a * (1. - t) + b * t
-}
-
 #[derive(AsBindGroup, TypeUuid, Debug, Clone, Default, Reflect)]
 #[uuid = "f690fd8e-d598-45ab-8225-97e2a3f056e0"]
 pub struct CloudBlobMaterial {
@@ -197,6 +218,9 @@ pub struct CloudBlobMaterial {
     pub scale: Vec3,
     #[uniform(0)]
     pub time: f32,
+    /// What the noise texels decode to, see [`bake::Encoded::range`]
+    #[uniform(0)]
+    pub noise_range: Vec2,
     #[texture(1, dimension = "3d")]
     #[sampler(2)]
     pub noise: Option<Handle<Image>>,
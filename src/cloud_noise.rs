@@ -0,0 +1,33 @@
+//! The cloud blob volume, shared by the app and the offline `bake` binary. The cloud
+//! material's textures are baked from the `.proctex.ron` files in `assets/clouds` instead,
+//! see [`crate::proctex`].
+
+use bevy::prelude::*;
+
+use crate::{
+    bake, cache,
+    noise::{self, NoiseSeed},
+};
+
+pub const BLOB_RES: usize = 200;
+
+/// [`cache::param_hash`] of what [`blob_volume`] bakes, bump the version when it changes.
+pub fn blob_params() -> u64 {
+    return cache::param_hash(&(4u32, 10f32.to_bits(), 100f32.to_bits(), 0.7f32.to_bits()));
+}
+
+/// The cloud blob volume, [`noise::fbmd`] blended with worley fbm.
+pub fn blob_volume(res: usize, seed: NoiseSeed) -> Vec<f32> {
+    let blob_noise = noise::FromFn(move |p: Vec3, _: Option<Vec3>| {
+        mix(
+            noise::fbmd(p, seed).x,
+            noise::wfbm(p * 0.5, Vec3::ONE * 100., seed),
+            0.7,
+        )
+    });
+    bake::noise_texture_3d((res, res, res), Vec3::splat(10.), false, &blob_noise)
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a * (1. - t) + b * t
+}
@@ -0,0 +1,576 @@
+//! Writes baked `R32Float`/`Rgba32Float` images to files other tools can open, and reads
+//! them back.
+//!
+//! - KTX2 keeps everything (format, 3D depth, cube faces, array layers, mips) and loads back
+//!   through Bevy's own KTX2 loader. It can also carry text key/value pairs.
+//! - OpenEXR stores every slice, face or layer as its own EXR layer, named so the kind of
+//!   texture can be recovered.
+//! - 16-bit PNG writes one file per slice. It's the only lossy one: values are clamped to
+//!   [0, 1] and quantised, so normalise the noise first. PNGs don't say what they hold,
+//!   the importer has to be told the [`TextureKind`].
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{
+            Extent3d, TextureDimension, TextureFormat, TextureViewDescriptor, TextureViewDimension,
+        },
+        texture::{CompressedImageFormats, ImageType},
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::mips;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextureKind {
+    D2,
+    D2Array,
+    D3,
+    Cube,
+}
+
+impl TextureKind {
+    pub fn of(image: &Image) -> Self {
+        let cube = image
+            .texture_view_descriptor
+            .as_ref()
+            .and_then(|view| view.dimension)
+            == Some(TextureViewDimension::Cube);
+        return match image.texture_descriptor.dimension {
+            TextureDimension::D3 => TextureKind::D3,
+            _ if cube => TextureKind::Cube,
+            _ if image.texture_descriptor.size.depth_or_array_layers > 1 => TextureKind::D2Array,
+            _ => TextureKind::D2,
+        };
+    }
+
+    fn layer_prefix(self) -> &'static str {
+        return match self {
+            TextureKind::D2 | TextureKind::D2Array => "layer",
+            TextureKind::D3 => "slice",
+            TextureKind::Cube => "face",
+        };
+    }
+}
+
+fn error(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn channels(format: TextureFormat) -> io::Result<usize> {
+    return match format {
+        TextureFormat::R32Float => Ok(1),
+        TextureFormat::Rgba32Float => Ok(4),
+        _ => Err(error(format!("can't export {:?} textures", format))),
+    };
+}
+
+fn format_for(channels: usize) -> io::Result<TextureFormat> {
+    return match channels {
+        1 => Ok(TextureFormat::R32Float),
+        4 => Ok(TextureFormat::Rgba32Float),
+        _ => Err(error(format!("can't import {} channel textures", channels))),
+    };
+}
+
+/// Texels of the top level, exports don't keep the mips.
+fn texels(image: &Image) -> Vec<f32> {
+    mips::base_level(image)
+        .chunks_exact(4)
+        .map(|b| f32::from_ne_bytes(b.try_into().unwrap()))
+        .collect()
+}
+
+/// Width, height and slice/face/layer count.
+fn extent(image: &Image) -> (u32, u32, u32) {
+    let size = image.texture_descriptor.size;
+    return (size.width, size.height, size.depth_or_array_layers);
+}
+
+fn new_image(
+    kind: TextureKind,
+    extent: (u32, u32, u32),
+    channels: usize,
+    data: &[f32],
+) -> io::Result<Image> {
+    let (width, height, layers) = extent;
+    if data.len() != (width * height * layers) as usize * channels {
+        return Err(error("texel count doesn't match the size"));
+    }
+    let mut image = Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: layers,
+        },
+        match kind {
+            TextureKind::D3 => TextureDimension::D3,
+            _ => TextureDimension::D2,
+        },
+        data.iter().flat_map(|f| f.to_ne_bytes()).collect(),
+        format_for(channels)?,
+    );
+    if kind == TextureKind::Cube {
+        image.texture_view_descriptor = Some(TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            ..default()
+        });
+    }
+    return Ok(image);
+}
+
+// https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const VK_FORMAT_R32_SFLOAT: u32 = 100;
+const VK_FORMAT_R32G32B32A32_SFLOAT: u32 = 109;
+
+/// Writes `image` as an uncompressed KTX2 file, mips included, with `key_values` in its
+/// key/value data (see [`ktx2_key_values`]).
+pub fn write_ktx2(
+    image: &Image,
+    key_values: &[(&str, &str)],
+    path: impl AsRef<Path>,
+) -> io::Result<()> {
+    let channels = channels(image.texture_descriptor.format)?;
+    let kind = TextureKind::of(image);
+    let (width, height, layers) = extent(image);
+    let (depth, layer_count, face_count) = match kind {
+        TextureKind::D2 => (0, 0, 1),
+        TextureKind::D2Array => (0, layers, 1),
+        TextureKind::D3 => (layers, 0, 1),
+        TextureKind::Cube => (0, layers / 6, 6),
+    };
+
+    // basic data format descriptor, one 32 bit float sample per channel
+    let mut dfd = vec![
+        0,
+        2 | ((24 + 16 * channels as u32) << 16),
+        // RGBSDA color model, BT.709 primaries, linear transfer
+        1 | (1 << 8) | (1 << 16),
+        0,
+        4 * channels as u32,
+        0,
+    ];
+    for (i, channel_id) in [0, 1, 2, 15].iter().take(channels).enumerate() {
+        // float and signed flags on the channel type
+        dfd.push((i as u32 * 32) | (31 << 16) | ((0xC0 | channel_id) << 24));
+        dfd.push(0);
+        dfd.push((-1.0f32).to_bits());
+        dfd.push(1.0f32.to_bits());
+    }
+    // sorted by key, every entry NUL terminated and padded to 4 bytes
+    let mut key_values = key_values.to_vec();
+    key_values.sort();
+    let mut kvd = Vec::new();
+    for (key, value) in key_values {
+        kvd.extend((key.len() as u32 + value.len() as u32 + 2).to_le_bytes());
+        kvd.extend(key.bytes().chain([0]).chain(value.bytes()).chain([0]));
+        kvd.resize(kvd.len().div_ceil(4) * 4, 0);
+    }
+    // level data has to be aligned to the texel size, smallest level first
+    let align = |offset: u64| offset.div_ceil(16) * 16;
+    let levels = level_data(image);
+    let dfd_offset = 80 + 24 * levels.len() as u64;
+    let dfd_length = 4 + 4 * dfd.len() as u32;
+    let kvd_offset = match kvd.len() {
+        0 => 0,
+        _ => dfd_offset as u32 + dfd_length,
+    };
+    let mut level_offsets = vec![0; levels.len()];
+    let mut end = align(dfd_offset + dfd_length as u64 + kvd.len() as u64);
+    for (level, data) in levels.iter().enumerate().rev() {
+        level_offsets[level] = end;
+        end = align(end + data.len() as u64);
+    }
+
+    let mut bytes = KTX2_IDENTIFIER.to_vec();
+    let format = match channels {
+        1 => VK_FORMAT_R32_SFLOAT,
+        _ => VK_FORMAT_R32G32B32A32_SFLOAT,
+    };
+    for v in [
+        format,
+        4,
+        width,
+        height,
+        depth,
+        layer_count,
+        face_count,
+        levels.len() as u32,
+        0,
+    ] {
+        bytes.extend(v.to_le_bytes());
+    }
+    for v in [dfd_offset as u32, dfd_length, kvd_offset, kvd.len() as u32] {
+        bytes.extend(v.to_le_bytes());
+    }
+    // no supercompression global data
+    bytes.extend([0; 16]);
+    for (offset, data) in level_offsets.iter().zip(&levels) {
+        for v in [*offset, data.len() as u64, data.len() as u64] {
+            bytes.extend(v.to_le_bytes());
+        }
+    }
+    bytes.extend(dfd_length.to_le_bytes());
+    for v in dfd {
+        bytes.extend(v.to_le_bytes());
+    }
+    bytes.extend(kvd);
+    for (level, data) in levels.iter().enumerate().rev() {
+        bytes.resize(level_offsets[level] as usize, 0);
+        bytes.extend(data);
+    }
+    return fs::write(path, bytes);
+}
+
+/// Little endian bytes of every mip level, each holding all layers or faces the way
+/// KTX2 stores them. `Image` keeps the levels of a layer together instead.
+fn level_data(image: &Image) -> Vec<Vec<u8>> {
+    let levels = image.texture_descriptor.mip_level_count;
+    let level_bytes: Vec<usize> = (0..levels).map(|l| mips::level_bytes(image, l)).collect();
+    let layer_stride: usize = level_bytes.iter().sum();
+    let layers = image.data.len() / layer_stride;
+    let mut start = 0;
+    return level_bytes
+        .iter()
+        .map(|len| {
+            let level = (0..layers)
+                .flat_map(|layer| &image.data[layer * layer_stride + start..][..*len])
+                .copied()
+                .collect::<Vec<u8>>()
+                .chunks_exact(4)
+                .flat_map(|b| f32::from_ne_bytes(b.try_into().unwrap()).to_le_bytes())
+                .collect();
+            start += len;
+            level
+        })
+        .collect();
+}
+
+/// Reads a KTX2 file written by [`write_ktx2`].
+pub fn read_ktx2(path: impl AsRef<Path>) -> io::Result<Image> {
+    return ktx2_image(&fs::read(path)?);
+}
+
+/// [`read_ktx2`] for a file that's already in memory.
+pub fn ktx2_image(bytes: &[u8]) -> io::Result<Image> {
+    return Image::from_buffer(
+        bytes,
+        ImageType::Extension("ktx2"),
+        CompressedImageFormats::NONE,
+        false,
+    )
+    .map_err(error);
+}
+
+/// The key/value pairs of a KTX2 file, in file order. Values are read as text.
+pub fn ktx2_key_values(bytes: &[u8]) -> io::Result<Vec<(String, String)>> {
+    let truncated = || error("truncated KTX2 file");
+    if !bytes.starts_with(&KTX2_IDENTIFIER) {
+        return Err(error("not a KTX2 file"));
+    }
+    let u32_at = |bytes: &[u8], offset: usize| {
+        let b = bytes.get(offset..offset + 4).ok_or_else(truncated)?;
+        return Ok::<_, io::Error>(u32::from_le_bytes(b.try_into().unwrap()) as usize);
+    };
+    let (start, length) = (u32_at(bytes, 56)?, u32_at(bytes, 60)?);
+    let kvd = bytes.get(start..start + length).ok_or_else(truncated)?;
+    let text = |b: &[u8]| String::from_utf8_lossy(b.strip_suffix(&[0]).unwrap_or(b)).into_owned();
+    let mut key_values = Vec::new();
+    let mut offset = 0;
+    while offset < kvd.len() {
+        let length = u32_at(kvd, offset)?;
+        let entry = kvd
+            .get(offset + 4..offset + 4 + length)
+            .ok_or_else(truncated)?;
+        let Some(split) = entry.iter().position(|b| *b == 0) else {
+            return Err(error("KTX2 key without a terminator"));
+        };
+        key_values.push((text(&entry[..split]), text(&entry[split + 1..])));
+        offset = (offset + 4 + length).div_ceil(4) * 4;
+    }
+    return Ok(key_values);
+}
+
+const EXR_CHANNELS: [&str; 4] = ["R", "G", "B", "A"];
+
+/// Writes `image` as a lossless OpenEXR file, one EXR layer per slice, face or layer.
+pub fn write_exr(image: &Image, path: impl AsRef<Path>) -> io::Result<()> {
+    use exr::prelude::*;
+
+    let channels = channels(image.texture_descriptor.format)?;
+    let kind = TextureKind::of(image);
+    let (width, height, layers) = extent(image);
+    let (width, height) = (width as usize, height as usize);
+    let data = texels(image);
+    let slice_len = width * height * channels;
+
+    let exr_layers: Vec<_> = data
+        .chunks_exact(slice_len)
+        .enumerate()
+        .map(|(i, slice)| {
+            let list = EXR_CHANNELS
+                .iter()
+                .take(channels)
+                .enumerate()
+                .map(|(c, name)| {
+                    let samples = slice.iter().skip(c).step_by(channels).copied().collect();
+                    AnyChannel::new(*name, FlatSamples::F32(samples))
+                })
+                .collect();
+            Layer::new(
+                (width, height),
+                LayerAttributes::named(format!("{}.{}", kind.layer_prefix(), i).as_str()),
+                Encoding::SMALL_LOSSLESS,
+                AnyChannels::sort(list),
+            )
+        })
+        .collect();
+    assert_eq!(exr_layers.len(), layers as usize);
+
+    return Image::from_layers(
+        ImageAttributes::new(IntegerBounds::from_dimensions((width, height))),
+        exr_layers,
+    )
+    .write()
+    .to_file(path)
+    .map_err(error);
+}
+
+/// Reads an OpenEXR file written by [`write_exr`].
+pub fn read_exr(path: impl AsRef<Path>) -> io::Result<Image> {
+    use exr::prelude::{ReadChannels, ReadLayers};
+
+    let exr = exr::prelude::read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .all_channels()
+        .all_layers()
+        .all_attributes()
+        .from_file(path)
+        .map_err(error)?;
+
+    let first = exr.layer_data.first().ok_or_else(|| error("no layers"))?;
+    let name = first
+        .attributes
+        .layer_name
+        .as_ref()
+        .map(|name| name.to_string())
+        .unwrap_or_default();
+    let kind = match name.split('.').next() {
+        Some("slice") => TextureKind::D3,
+        Some("face") => TextureKind::Cube,
+        _ if exr.layer_data.len() > 1 => TextureKind::D2Array,
+        _ => TextureKind::D2,
+    };
+    let (width, height) = (first.size.width(), first.size.height());
+    let channels = first.channel_data.list.len();
+
+    let mut data = Vec::with_capacity(width * height * channels * exr.layer_data.len());
+    for layer in &exr.layer_data {
+        let samples: Vec<Vec<f32>> = EXR_CHANNELS
+            .iter()
+            .take(channels)
+            .map(|name| {
+                let channel = layer
+                    .channel_data
+                    .list
+                    .iter()
+                    .find(|channel| channel.name.eq(name))
+                    .ok_or_else(|| error(format!("missing channel {}", name)))?;
+                Ok(channel.sample_data.values_as_f32().collect())
+            })
+            .collect::<io::Result<_>>()?;
+        for i in 0..width * height {
+            data.extend(samples.iter().map(|channel| channel[i]));
+        }
+    }
+    let extent = (width as u32, height as u32, exr.layer_data.len() as u32);
+    return new_image(kind, extent, channels, &data);
+}
+
+/// `path` for a single 2D image, `<stem>.<i>.png` next to it otherwise.
+fn png_slice_path(path: &Path, kind: TextureKind, i: u32) -> PathBuf {
+    if kind == TextureKind::D2 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    return path.with_file_name(format!("{}.{}.png", stem, i));
+}
+
+/// Writes `image` as 16-bit grayscale or RGBA PNGs, one per slice, face or layer.
+pub fn write_png16(image: &Image, path: impl AsRef<Path>) -> io::Result<()> {
+    let channels = channels(image.texture_descriptor.format)?;
+    let kind = TextureKind::of(image);
+    let (width, height, _) = extent(image);
+    let data = texels(image);
+
+    for (i, slice) in data
+        .chunks_exact((width * height) as usize * channels)
+        .enumerate()
+    {
+        let file = File::create(png_slice_path(path.as_ref(), kind, i as u32))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+        encoder.set_color(match channels {
+            1 => png::ColorType::Grayscale,
+            _ => png::ColorType::Rgba,
+        });
+        encoder.set_depth(png::BitDepth::Sixteen);
+        let bytes: Vec<u8> = slice
+            .iter()
+            .flat_map(|v| ((v.clamp(0.0, 1.0) * 65535.0).round() as u16).to_be_bytes())
+            .collect();
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&bytes))
+            .map_err(error)?;
+    }
+    return Ok(());
+}
+
+/// Reads the PNGs written by [`write_png16`] for a texture of the given kind.
+pub fn read_png16(path: impl AsRef<Path>, kind: TextureKind) -> io::Result<Image> {
+    let mut data = Vec::new();
+    let mut extent = (0, 0, 0);
+    let mut channels = 0;
+    loop {
+        let file = match File::open(png_slice_path(path.as_ref(), kind, extent.2)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && extent.2 > 0 => break,
+            Err(e) => return Err(e),
+        };
+        let mut reader = png::Decoder::new(file).read_info().map_err(error)?;
+        let mut bytes = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut bytes).map_err(error)?;
+        if info.bit_depth != png::BitDepth::Sixteen {
+            return Err(error("not a 16-bit PNG"));
+        }
+        channels = info.color_type.samples();
+        extent = (info.width, info.height, extent.2 + 1);
+        data.extend(
+            bytes[..info.buffer_size()]
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / 65535.0),
+        );
+        if kind == TextureKind::D2 {
+            break;
+        }
+    }
+    return new_image(kind, extent, channels, &data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        return std::env::temp_dir().join(format!("resume-export-{}-{}", std::process::id(), name));
+    }
+
+    // Values a float exporter could get wrong: signs, tiny and huge magnitudes, and
+    // bits that don't survive a trip through f16
+    fn test_image(kind: TextureKind, extent: (u32, u32, u32), channels: usize) -> Image {
+        let len = (extent.0 * extent.1 * extent.2) as usize * channels;
+        let data: Vec<f32> = (0..len)
+            .map(|i| match i % 5 {
+                0 => i as f32 * -0.731,
+                1 => 1e-30 * i as f32,
+                2 => 3.0e38 / (i + 1) as f32,
+                3 => f32::from_bits(0x3f80_0001 + i as u32),
+                _ => (i as f32).sin(),
+            })
+            .collect();
+        return new_image(kind, extent, channels, &data).unwrap();
+    }
+
+    fn assert_same(read: &Image, written: &Image) {
+        assert_eq!(TextureKind::of(read), TextureKind::of(written));
+        assert_eq!(extent(read), extent(written));
+        assert_eq!(
+            read.texture_descriptor.format,
+            written.texture_descriptor.format
+        );
+        let bits = |image: &Image| {
+            texels(image)
+                .iter()
+                .map(|f| f.to_bits())
+                .collect::<Vec<_>>()
+        };
+        assert!(bits(read) == bits(written), "texels changed");
+    }
+
+    #[test]
+    fn exr_is_lossless() {
+        for (kind, extent, channels) in [
+            (TextureKind::D2, (7, 5, 1), 1),
+            (TextureKind::D2, (4, 3, 1), 4),
+            (TextureKind::D2Array, (3, 4, 5), 1),
+            (TextureKind::D3, (6, 6, 6), 4),
+            (TextureKind::Cube, (4, 4, 6), 4),
+        ] {
+            let image = test_image(kind, extent, channels);
+            let path = temp_path(&format!("{:?}-{}.exr", kind, channels));
+            write_exr(&image, &path).unwrap();
+            let read = read_exr(&path);
+            fs::remove_file(&path).unwrap();
+            assert_same(&read.unwrap(), &image);
+        }
+    }
+
+    #[test]
+    fn ktx2_keeps_texels_and_key_values() {
+        for (kind, extent, channels) in [
+            (TextureKind::D2, (7, 5, 1), 1),
+            (TextureKind::D2Array, (3, 4, 5), 1),
+            (TextureKind::D3, (6, 6, 6), 4),
+            (TextureKind::Cube, (4, 4, 6), 4),
+        ] {
+            let image = test_image(kind, extent, channels);
+            let path = temp_path(&format!("{:?}-{}.ktx2", kind, channels));
+            write_ktx2(&image, &[("b", "2"), ("a", ""), ("ccc", "3.5 -1")], &path).unwrap();
+            let bytes = fs::read(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_same(&ktx2_image(&bytes).unwrap(), &image);
+            let expected = [("a", ""), ("b", "2"), ("ccc", "3.5 -1")]
+                .map(|(k, v)| (k.to_string(), v.to_string()));
+            assert_eq!(ktx2_key_values(&bytes).unwrap(), expected);
+        }
+
+        let path = temp_path("plain.ktx2");
+        write_ktx2(&test_image(TextureKind::D2, (2, 2, 1), 4), &[], &path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(ktx2_key_values(&bytes).unwrap().is_empty());
+        assert!(ktx2_key_values(&bytes[..40]).is_err());
+    }
+
+    #[test]
+    fn png16_quantises_to_16_bits() {
+        let image = new_image(
+            TextureKind::D3,
+            (5, 4, 3),
+            4,
+            &(0..240).map(|i| i as f32 / 239.0).collect::<Vec<_>>(),
+        )
+        .unwrap();
+        let path = temp_path("quantised.png");
+        write_png16(&image, &path).unwrap();
+        let read = read_png16(&path, TextureKind::D3).unwrap();
+        for i in 0..3 {
+            fs::remove_file(png_slice_path(&path, TextureKind::D3, i)).unwrap();
+        }
+        assert_eq!(extent(&read), extent(&image));
+        for (a, b) in texels(&read).iter().zip(texels(&image)) {
+            assert!((a - b).abs() <= 0.5 / 65535.0, "{} read back as {}", b, a);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! The texture of the fin clouds: their base mesh drawn into its own UV layout, with
+//! worley fbm of the surface position in r, opacity in g and a height gradient in b. The
+//! `bake` binary prebakes it, `fin_cloud.rs` in the app grows the fins off the same mesh.
+
+use bevy::{
+    math::{dvec2, dvec3, vec4, DVec2, DVec3},
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use rayon::prelude::*;
+
+use crate::{
+    bake, cache,
+    mips::{self, MipFilter},
+    noise::{self, NoiseSeed},
+};
+
+pub const FIN_RES: (usize, usize) = (2048, 2048);
+
+/// The low poly sphere the fins grow out of.
+pub fn base_mesh() -> Mesh {
+    return shape::UVSphere {
+        radius: 1.,
+        sectors: 8,
+        stacks: 4,
+    }
+    .into();
+}
+
+/// [`cache::param_hash`] of what [`fin_image`] bakes of the [`base_mesh`], bump the
+/// version when it changes.
+pub fn fin_params() -> u64 {
+    return cache::param_hash(&(2u32, 8u32, 4u32, 4f32.to_bits(), 1000f32.to_bits()));
+}
+
+/// Surface position under every texel of `mesh`'s UV layout, row by row, `None` where no
+/// triangle covers it. `None` for meshes without positions, UVs or indices.
+pub fn rasterize_uv(mesh: &Mesh, resolution: (usize, usize)) -> Option<Vec<Option<DVec3>>> {
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+    let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0) else {
+        return None;
+    };
+    let indices: Vec<usize> = match mesh.indices()? {
+        Indices::U32(indices) => indices.iter().map(|i| *i as usize).collect(),
+        Indices::U16(indices) => indices.iter().map(|i| *i as usize).collect(),
+    };
+    let (width, height) = resolution;
+    let scale = dvec2(width as f64, height as f64);
+    let mut texture = vec![None; width * height];
+    for triangle in indices.chunks_exact(3) {
+        let position = |i: usize| DVec3::from(positions[triangle[i]].map(f64::from));
+        let uv = |i: usize| DVec2::from(uvs[triangle[i]].map(f64::from)) * scale;
+        let (a, b, c) = (uv(0), uv(1), uv(2));
+        raster(a, b, c, |x| {
+            if x.x < 0. || x.y < 0. || x.x >= scale.x || x.y >= scale.y {
+                return;
+            }
+            let t = bary_lerp(x, a, b, c);
+            let p = position(0) * t.x + position(1) * t.y + position(2) * t.z;
+            texture[x.y as usize * width + x.x as usize] = Some(p);
+        });
+    }
+    return Some(texture);
+}
+
+/// The fin texture of `mesh`, see the module docs.
+pub fn fin_image(mesh: &Mesh, resolution: (usize, usize), seed: NoiseSeed) -> Option<Image> {
+    let positions = rasterize_uv(mesh, resolution)?;
+    let texels: Vec<Vec4> = positions
+        .par_iter()
+        .map(|p| {
+            let p = p.unwrap_or(DVec3::ONE);
+            vec4(
+                noise::wfbm((p * 4.).as_vec3(), Vec3::splat(1000.), seed),
+                1.,
+                (p.y / p.length() * 1.5 + 1.) as f32,
+                1.,
+            )
+        })
+        .collect();
+    let image = Image::new(
+        Extent3d {
+            width: resolution.0 as u32,
+            height: resolution.1 as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        bake::rgba32_bytes(&texels),
+        TextureFormat::Rgba32Float,
+    );
+    return Some(mips::with_mips(image, MipFilter::Kaiser, false));
+}
+
+fn bary_lerp(t: DVec2, a: DVec2, b: DVec2, c: DVec2) -> DVec3 {
+    let alpha = ((b.y - c.y) * (t.x - c.x) + (c.x - b.x) * (t.y - c.y))
+        / ((b.y - c.y) * (a.x - c.x) + (c.x - b.x) * (a.y - c.y));
+    let beta = ((c.y - a.y) * (t.x - c.x) + (a.x - c.x) * (t.y - c.y))
+        / ((b.y - c.y) * (a.x - c.x) + (c.x - b.x) * (a.y - c.y));
+    return dvec3(alpha, beta, 1. - alpha - beta);
+}
+
+//https://iquilezles.org/articles/distfunctions2d/
+fn sd_triangle(p: DVec2, p0: DVec2, p1: DVec2, p2: DVec2) -> f64 {
+    let e0 = p1 - p0;
+    let e1 = p2 - p1;
+    let e2 = p0 - p2;
+    let v0 = p - p0;
+    let v1 = p - p1;
+    let v2 = p - p2;
+    let pq0 = v0 - e0 * (v0.dot(e0) / e0.dot(e0)).clamp(0.0, 1.0);
+    let pq1 = v1 - e1 * (v1.dot(e1) / e1.dot(e1)).clamp(0.0, 1.0);
+    let pq2 = v2 - e2 * (v2.dot(e2) / e2.dot(e2)).clamp(0.0, 1.0);
+    let s = (e0.x * e2.y - e0.y * e2.x).signum();
+    let d = dvec2(pq0.dot(pq0), s * (v0.x * e0.y - v0.y * e0.x))
+        .min(dvec2(pq1.dot(pq1), s * (v1.x * e1.y - v1.y * e1.x)))
+        .min(dvec2(pq2.dot(pq2), s * (v2.x * e2.y - v2.y * e2.x)));
+    return -(d.x).sqrt() * (d.y).signum();
+}
+
+// Calls `f` with every texel within a texel of the triangle
+fn raster<F: FnMut(DVec2)>(a: DVec2, b: DVec2, c: DVec2, mut f: F) {
+    let lower = a.min(b).min(c).floor();
+    let upper = a.max(b).max(c);
+    let mut x = lower.x;
+    while x < upper.x {
+        let mut y = lower.y;
+        while y < upper.y {
+            if sd_triangle(dvec2(x, y), a, b, c) <= 1. {
+                f(dvec2(x, y));
+            }
+            y += 1.;
+        }
+        x += 1.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_mesh_covers_its_uv_layout() {
+        let (width, height) = (64, 32);
+        let positions = rasterize_uv(&base_mesh(), (width, height)).unwrap();
+        assert_eq!(positions.len(), width * height);
+        for (i, p) in positions.iter().enumerate() {
+            // the pole stacks have one triangle per sector, half their UV quads are empty
+            let row = i / width;
+            let Some(p) = p else {
+                assert!(
+                    row < height / 4 || row >= height * 3 / 4,
+                    "texel {} isn't covered",
+                    i
+                );
+                continue;
+            };
+            // the flat faces of the sphere sag towards its centre, and texels just off a
+            // triangle's edge extrapolate a little past it
+            let r = p.length();
+            assert!((0.7..=1.05).contains(&r), "texel {} at radius {}", i, r);
+        }
+    }
+}
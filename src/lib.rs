@@ -0,0 +1,16 @@
+//! The noise and texture baking code, split out of the app so it can be benchmarked and
+//! run offline by the `bake` binary.
+
+#![allow(clippy::needless_return)]
+
+pub mod async_bake;
+pub mod bake;
+pub mod cache;
+pub mod cloud_noise;
+pub mod export;
+pub mod fin_texture;
+pub mod mips;
+pub mod noise;
+pub mod prebaked;
+pub mod proctex;
+pub mod sdf;
@@ -1,5 +1,7 @@
 // orbital scene
 
+#![allow(clippy::needless_return)]
+
 use std::f32::consts::PI;
 
 use bevy::{
@@ -17,14 +19,12 @@ use cloud::RMCloud;
 // use skybox::{CubemapMaterial, SkyBoxPlugin};
 // use water::WaterPlugin;
 mod camera;
-// mod cloud_blob;
-// mod fin_cloud;
 mod cloud;
-mod noise;
+mod cloud_blob;
+// mod fin_cloud;
 mod noise_shader;
-mod sdf;
+mod rm_cloud;
 mod skybox;
-mod test_cloud_shader;
 mod water;
 
 fn main() {
@@ -49,6 +49,10 @@ fn main() {
                 }),
         )
         .add_plugin(WorldInspectorPlugin::new())
+        .add_plugin(noise_shader::NoiseShaderPlugin)
+        .add_plugin(resume::noise::NoiseGraphPlugin)
+        .add_plugin(resume::async_bake::AsyncBakePlugin)
+        .add_plugin(resume::proctex::ProcTexPlugin)
         .add_plugin(cloud::RMCloudPlugin)
         // .add_plugin(fin_cloud::FinCloudPlugin)
         // .add_plugin(CloudBlobPlugin)
@@ -0,0 +1,427 @@
+//! CPU mip chains for baked images.
+//!
+//! Each level halves the previous one with a separable filter, one axis at a time. 2D
+//! images (arrays and cubes included) are filtered layer by layer, 3D images shrink in
+//! depth too. With `wrap` set the filter reads across the edges, so tiling noise keeps
+//! tiling at every level instead of picking up a seam.
+//!
+//! The data ends up in the order wgpu uploads it: for each layer, every mip level.
+
+use std::f32::consts::PI;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{TextureDimension, TextureFormat},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum MipFilter {
+    /// Average of each 2×2(×2) block, cheap but slightly blurry and aliased
+    Box,
+    /// Kaiser windowed sinc, sharper with less aliasing, can ring a little
+    #[default]
+    Kaiser,
+}
+
+const KAISER_WIDTH: f32 = 3.0;
+const KAISER_ALPHA: f32 = 4.0;
+
+impl MipFilter {
+    /// Half width of the kernel in destination texels.
+    fn support(self) -> f32 {
+        return match self {
+            MipFilter::Box => 0.5,
+            MipFilter::Kaiser => KAISER_WIDTH,
+        };
+    }
+
+    /// Weight of a source texel `d` destination texels from the one being filtered.
+    fn weight(self, d: f32) -> f32 {
+        return match self {
+            MipFilter::Box => {
+                if d.abs() < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            MipFilter::Kaiser => {
+                let x = d / KAISER_WIDTH;
+                if x.abs() >= 1.0 {
+                    return 0.0;
+                }
+                bessel_i0(KAISER_ALPHA * (1.0 - x * x).sqrt()) / bessel_i0(KAISER_ALPHA) * sinc(d)
+            }
+        };
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-4 {
+        return 1.0;
+    }
+    return (PI * x).sin() / (PI * x);
+}
+
+// Modified Bessel function of the first kind, order 0, by its power series
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+    while term > sum * 1e-7 {
+        term *= (x / (2.0 * k)) * (x / (2.0 * k));
+        sum += term;
+        k += 1.0;
+    }
+    return sum;
+}
+
+/// Source taps and normalised weights for every destination texel along one axis.
+fn kernel(filter: MipFilter, src: usize, dst: usize, wrap: bool) -> Vec<Vec<(usize, f32)>> {
+    let scale = src as f32 / dst as f32;
+    let reach = (filter.support() * scale).ceil() as isize + 1;
+    (0..dst)
+        .map(|x| {
+            let center = (x as f32 + 0.5) * scale;
+            let mut taps: Vec<(usize, f32)> = (center as isize - reach..=center as isize + reach)
+                .filter_map(|j| {
+                    let weight = filter.weight((j as f32 + 0.5 - center) / scale);
+                    if weight == 0.0 {
+                        return None;
+                    }
+                    let i = if wrap {
+                        j.rem_euclid(src as isize)
+                    } else {
+                        j.clamp(0, src as isize - 1)
+                    };
+                    Some((i as usize, weight))
+                })
+                .collect();
+            let total: f32 = taps.iter().map(|(_, w)| w).sum();
+            for tap in &mut taps {
+                tap.1 /= total;
+            }
+            taps
+        })
+        .collect()
+}
+
+/// Shrinks `axis` (0 = x, 1 = y, 2 = z) of a `size` block of `channels` wide texels.
+fn downsample_axis(
+    data: &[f32],
+    size: [usize; 3],
+    channels: usize,
+    axis: usize,
+    filter: MipFilter,
+    wrap: bool,
+) -> (Vec<f32>, [usize; 3]) {
+    let mut out_size = size;
+    out_size[axis] = (size[axis] / 2).max(1);
+    if out_size[axis] == size[axis] {
+        return (data.to_vec(), size);
+    }
+    let kernel = kernel(filter, size[axis], out_size[axis], wrap);
+    let stride = [1, size[0], size[0] * size[1]][axis];
+    let mut out = vec![0.0; out_size.iter().product::<usize>() * channels];
+    for z in 0..out_size[2] {
+        for y in 0..out_size[1] {
+            for x in 0..out_size[0] {
+                let p = [x, y, z];
+                let mut base = [x, y, z];
+                base[axis] = 0;
+                let src = base[0] + base[1] * size[0] + base[2] * size[0] * size[1];
+                let dst = x + y * out_size[0] + z * out_size[0] * out_size[1];
+                for (i, w) in &kernel[p[axis]] {
+                    let s = (src + i * stride) * channels;
+                    for c in 0..channels {
+                        out[dst * channels + c] += data[s + c] * w;
+                    }
+                }
+            }
+        }
+    }
+    return (out, out_size);
+}
+
+fn channels(format: TextureFormat) -> usize {
+    return match format {
+        TextureFormat::R32Float | TextureFormat::R16Unorm | TextureFormat::R8Unorm => 1,
+        TextureFormat::Rgba32Float | TextureFormat::Rgba16Unorm | TextureFormat::Rgba8Unorm => 4,
+        _ => panic!("can't generate mips for {:?}", format),
+    };
+}
+
+fn is_unorm(format: TextureFormat) -> bool {
+    return matches!(
+        format,
+        TextureFormat::R16Unorm
+            | TextureFormat::Rgba16Unorm
+            | TextureFormat::R8Unorm
+            | TextureFormat::Rgba8Unorm
+    );
+}
+
+fn decode(bytes: &[u8], format: TextureFormat) -> Vec<f32> {
+    return match format {
+        TextureFormat::R16Unorm | TextureFormat::Rgba16Unorm => bytes
+            .chunks_exact(2)
+            .map(|b| u16::from_ne_bytes([b[0], b[1]]) as f32 / 65535.0)
+            .collect(),
+        TextureFormat::R8Unorm | TextureFormat::Rgba8Unorm => {
+            bytes.iter().map(|b| *b as f32 / 255.0).collect()
+        }
+        _ => bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_ne_bytes(b.try_into().unwrap()))
+            .collect(),
+    };
+}
+
+fn encode(values: &[f32], format: TextureFormat) -> Vec<u8> {
+    return match format {
+        TextureFormat::R16Unorm | TextureFormat::Rgba16Unorm => values
+            .iter()
+            .flat_map(|v| ((v.clamp(0.0, 1.0) * 65535.0).round() as u16).to_ne_bytes())
+            .collect(),
+        TextureFormat::R8Unorm | TextureFormat::Rgba8Unorm => values
+            .iter()
+            .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect(),
+        _ => values.iter().flat_map(|v| v.to_ne_bytes()).collect(),
+    };
+}
+
+/// Number of levels down to 1×1(×1).
+pub fn mip_level_count(image: &Image) -> u32 {
+    let size = image.texture_descriptor.size;
+    let depth = match image.texture_descriptor.dimension {
+        TextureDimension::D3 => size.depth_or_array_layers,
+        _ => 1,
+    };
+    return 32 - size.width.max(size.height).max(depth).leading_zeros();
+}
+
+/// Byte length of mip `level` of a single layer.
+pub fn level_bytes(image: &Image, level: u32) -> usize {
+    let size = image.texture_descriptor.size;
+    let format = image.texture_descriptor.format;
+    let depth = match image.texture_descriptor.dimension {
+        TextureDimension::D3 => size.depth_or_array_layers,
+        _ => 1,
+    };
+    let texel = format.describe().block_size as usize;
+    let texels = [size.width, size.height, depth]
+        .iter()
+        .map(|n| (n >> level).max(1) as usize)
+        .product::<usize>();
+    return texels * texel;
+}
+
+fn layer_bytes(image: &Image) -> usize {
+    return level_bytes(image, 0);
+}
+
+/// The top level of every layer, without the mips, as `Image::new` lays it out.
+pub fn base_level(image: &Image) -> Vec<u8> {
+    let layer_bytes = layer_bytes(image);
+    let layers = match image.texture_descriptor.dimension {
+        TextureDimension::D3 => 1,
+        _ => image.texture_descriptor.size.depth_or_array_layers as usize,
+    };
+    let stride = image.data.len() / layers;
+    return (0..layers)
+        .flat_map(|layer| &image.data[layer * stride..layer * stride + layer_bytes])
+        .copied()
+        .collect();
+}
+
+/// Appends a full mip chain to a single level image and sets `mip_level_count`.
+pub fn generate_mips(image: &mut Image, filter: MipFilter, wrap: bool) {
+    if image.texture_descriptor.mip_level_count > 1 {
+        return;
+    }
+    let format = image.texture_descriptor.format;
+    let channels = channels(format);
+    let size = image.texture_descriptor.size;
+    let volume = image.texture_descriptor.dimension == TextureDimension::D3;
+    let (layers, depth) = if volume {
+        (1, size.depth_or_array_layers as usize)
+    } else {
+        (size.depth_or_array_layers as usize, 1)
+    };
+    let levels = mip_level_count(image);
+    let layer_bytes = layer_bytes(image);
+
+    // the Kaiser filter overshoots at edges, clamp it where the format would so the next
+    // level is filtered from what's stored instead of the ringing
+    let unorm = is_unorm(format);
+
+    let mut data = Vec::with_capacity(image.data.len() * 2);
+    for layer in image.data.chunks_exact(layer_bytes).take(layers) {
+        data.extend_from_slice(layer);
+        let mut texels = decode(layer, format);
+        let mut level_size = [size.width as usize, size.height as usize, depth];
+        for _ in 1..levels {
+            for axis in 0..3 {
+                (texels, level_size) =
+                    downsample_axis(&texels, level_size, channels, axis, filter, wrap);
+            }
+            if unorm {
+                for texel in &mut texels {
+                    *texel = texel.clamp(0.0, 1.0);
+                }
+            }
+            data.extend(encode(&texels, format));
+        }
+    }
+    image.data = data;
+    image.texture_descriptor.mip_level_count = levels;
+}
+
+/// [`generate_mips`] for an image being built.
+pub fn with_mips(mut image: Image, filter: MipFilter, wrap: bool) -> Image {
+    generate_mips(&mut image, filter, wrap);
+    return image;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_resource::Extent3d;
+
+    use super::*;
+
+    fn image(
+        size: [u32; 3],
+        dimension: TextureDimension,
+        format: TextureFormat,
+        values: &[f32],
+    ) -> Image {
+        return Image::new(
+            Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: size[2],
+            },
+            dimension,
+            encode(values, format),
+            format,
+        );
+    }
+
+    // every level of the first layer, decoded
+    fn levels(image: &Image) -> Vec<Vec<f32>> {
+        let format = image.texture_descriptor.format;
+        let mut start = 0;
+        return (0..image.texture_descriptor.mip_level_count)
+            .map(|level| {
+                let end = start + level_bytes(image, level);
+                let texels = decode(&image.data[start..end], format);
+                start = end;
+                texels
+            })
+            .collect();
+    }
+
+    // doesn't repeat within the image, so rolling it shows
+    fn pattern(x: usize, y: usize) -> f32 {
+        return 0.5 + 0.4 * ((x * 7 + y * 13) % 17) as f32 / 17.0;
+    }
+
+    #[test]
+    fn constant_images_stay_constant() {
+        let cases = [
+            ([8, 4, 1], TextureDimension::D2, TextureFormat::R32Float),
+            ([4, 4, 3], TextureDimension::D2, TextureFormat::Rgba8Unorm),
+            ([8, 4, 2], TextureDimension::D3, TextureFormat::R16Unorm),
+        ];
+        for (size, dimension, format) in cases {
+            let texels = size.iter().product::<u32>() as usize * channels(format);
+            for filter in [MipFilter::Box, MipFilter::Kaiser] {
+                for wrap in [false, true] {
+                    let mut image = image(size, dimension, format, &vec![0.6; texels]);
+                    let base = decode(&image.data, format)[0];
+                    generate_mips(&mut image, filter, wrap);
+                    let levels = levels(&image);
+                    assert_eq!(levels.len() as u32, mip_level_count(&image));
+                    for level in levels {
+                        assert!(
+                            level.iter().all(|v| (v - base).abs() < 1e-6),
+                            "{:?} {:?} wrap {} drifted to {:?}",
+                            format,
+                            filter,
+                            wrap,
+                            level
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wrapped_edges_filter_like_the_middle() {
+        // rolled by half its size the seam is in the middle, so with the edges matching
+        // every level is the same image rolled by half of that level
+        let (width, height) = (16, 8);
+        let values = |shift: (usize, usize)| -> Vec<f32> {
+            return (0..width * height)
+                .map(|i| {
+                    pattern(
+                        (i % width + shift.0) % width,
+                        (i / width + shift.1) % height,
+                    )
+                })
+                .collect();
+        };
+        let size = [width as u32, height as u32, 1];
+        let mips = |values: &[f32]| {
+            let image = image(size, TextureDimension::D2, TextureFormat::R32Float, values);
+            return levels(&with_mips(image, MipFilter::Kaiser, true));
+        };
+        let (original, rolled) = (
+            mips(&values((0, 0))),
+            mips(&values((width / 2, height / 2))),
+        );
+        for (level, (original, rolled)) in original.iter().zip(&rolled).enumerate() {
+            let (w, h) = ((width >> level).max(1), (height >> level).max(1));
+            for (i, v) in rolled.iter().enumerate() {
+                let (x, y) = ((i % w + w / 2) % w, (i / w + h / 2) % h);
+                assert!(
+                    (v - original[x + y * w]).abs() < 1e-6,
+                    "level {} differs at ({}, {})",
+                    level,
+                    i % w,
+                    i / w
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unorm_levels_filter_the_clamped_level() {
+        // a hard step rings, every level should come from the one above as it's stored
+        let size = [32, 1, 1];
+        let values: Vec<f32> = (0..32).map(|x| (x % 8 < 4) as u32 as f32).collect();
+        let image = with_mips(
+            image(size, TextureDimension::D2, TextureFormat::R16Unorm, &values),
+            MipFilter::Kaiser,
+            false,
+        );
+        let mut expected = values;
+        let mut width = 32;
+        for level in levels(&image).iter().skip(1) {
+            let (filtered, size) =
+                downsample_axis(&expected, [width, 1, 1], 1, 0, MipFilter::Kaiser, false);
+            width = size[0];
+            expected = filtered.iter().map(|v| v.clamp(0.0, 1.0)).collect();
+            for (v, e) in level.iter().zip(&expected) {
+                assert!(
+                    (v - e).abs() <= 0.5 / 65535.0 + 1e-6,
+                    "{:?} {:?}",
+                    level,
+                    expected
+                );
+            }
+        }
+    }
+}
@@ -1,252 +1,350 @@
-#![allow(clippy::all)]
-#![allow(unused)]
-
-use std::f32::consts::{E, PI};
-
 use bevy::{
-    math::{dvec3, mat2, mat3, vec2, vec3, vec4, DVec3, Vec3Swizzles},
-    prelude::{Mat2, Mat3, Vec3, Vec4},
+    math::{mat2, mat3, vec2, vec3, vec4, DVec3, Vec3Swizzles},
+    prelude::{Mat2, Mat3, Reflect, ReflectResource, Resource, Vec2, Vec3, Vec4},
 };
+use serde::{Deserialize, Serialize};
+
+mod batch;
+mod blue;
+mod cellular;
+mod cloud_volume;
+mod curl;
+mod fractal;
+pub mod generic;
+mod gradient;
+mod graph;
+mod lanes;
+mod looping;
+mod noise_fn;
+mod stats;
+mod warp;
+mod wgsl;
+pub use batch::*;
+pub use blue::*;
+pub use cellular::*;
+pub use cloud_volume::*;
+pub use curl::*;
+pub use fractal::*;
+pub use generic::{Real, V3};
+pub use gradient::*;
+pub use graph::*;
+pub use lanes::*;
+pub use looping::*;
+pub use noise_fn::*;
+pub use stats::*;
+pub use warp::*;
+pub use wgsl::*;
+
+/// Seed shared by every noise function in this module, the same seed always
+/// produces the same field. A seed shifts the lattice the integer hashes see (see
+/// [`NoiseSeed::keys`]), so every seed gives the same kind of noise.
+#[derive(Resource, Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[reflect(Resource)]
+pub struct NoiseSeed(pub u32);
+
+impl NoiseSeed {
+    /// Added to the lattice cell on each axis before it's hashed, all zero for seed 0.
+    pub fn keys(self) -> [u32; 4] {
+        [0x9E37_79B9, 0x85EB_CA6B, 0xC2B2_AE35, 0x27D4_EB2F].map(|k| self.0.wrapping_mul(k))
+    }
+
+    /// The seed with its keys scrambled, for a second hash of the same lattice that
+    /// doesn't follow the first.
+    pub fn salted(self) -> NoiseSeed {
+        NoiseSeed(self.0 ^ 0x5BD1_E995)
+    }
+
+    /// Three values in [0, 1) for the lattice cell `p`, which should be whole numbers.
+    pub fn hash33(self, p: Vec3) -> Vec3 {
+        generic::hash33(V3::from(p), self).into()
+    }
+
+    pub fn hash22(self, p: Vec2) -> Vec2 {
+        self.hash33(p.extend(0.0)).xy()
+    }
+
+    pub fn hash44(self, p: Vec4) -> Vec4 {
+        let keys = self.keys();
+        let cell = p.to_array().map(Real::cell);
+        let h = generic::pcg4d(std::array::from_fn(|k| cell[k].wrapping_add(keys[k])));
+        Vec4::from_array(h.map(f32::unit))
+    }
+}
+
+pub fn value_noise(x: Vec3, seed: NoiseSeed) -> f32 {
+    generic::value_noise(x.into(), seed)
+}
+
+pub fn dvalue_noise(x: DVec3, seed: NoiseSeed) -> f64 {
+    generic::value_noise(x.into(), seed)
+}
+
+/// 8 octaves of [`noised`], every octave repeats with the same period `f`. The last
+/// octaves sample around 1e4, so this is only within about 1e-2 of [`dvalue_fbm`].
+pub fn value_fbm(p: Vec3, f: Vec3, seed: NoiseSeed) -> f32 {
+    generic::value_fbm(p.into(), f.into(), seed)
+}
 
-fn hash(p: Vec3) -> f32 {
-    // replace this by something better {
-    let mut p = (p * 0.3183099 + 0.1).fract();
-    p *= 17.0;
-    return (p.x * p.y * p.z * (p.x + p.y + p.z)).fract();
-}
-
-fn dhash(p: DVec3) -> f64 {
-    // replace this by something better {
-    let mut p = (p * 0.3183099 + 0.1).fract();
-    p *= 17.0;
-    return (p.x * p.y * p.z * (p.x + p.y + p.z)).fract();
-}
-
-pub fn value_noise(x: Vec3) -> f32 {
-    let i = x.floor();
-    let w = x.fract();
-    // cubic interpolation
-    let u = w * w * (3.0 - 2.0 * w);
-    let a = hash33(i + vec3(0.0, 0.0, 0.0)).x;
-    let b = hash33(i + vec3(1.0, 0.0, 0.0)).x;
-    let c = hash33(i + vec3(0.0, 1.0, 0.0)).x;
-    let d = hash33(i + vec3(1.0, 1.0, 0.0)).x;
-    let e = hash33(i + vec3(0.0, 0.0, 1.0)).x;
-    let f = hash33(i + vec3(1.0, 0.0, 1.0)).x;
-    let g = hash33(i + vec3(0.0, 1.0, 1.0)).x;
-    let h = hash33(i + vec3(1.0, 1.0, 1.0)).x;
-
-    let k0 = a;
-    let k1 = b - a;
-    let k2 = c - a;
-    let k3 = e - a;
-    let k4 = a - b - c + d;
-    let k5 = a - c - e + g;
-    let k6 = a - b - e + f;
-    let k7 = -a + b + c - d + e - f - g + h;
-
-    return k0
-        + k1 * u.x
-        + k2 * u.y
-        + k3 * u.z
-        + k4 * u.x * u.y
-        + k5 * u.y * u.z
-        + k6 * u.z * u.x
-        + k7 * u.x * u.y * u.z;
-}
-
-pub fn value_fbm(p: Vec3, f: Vec3) -> f32 {
-    let mut p = p.as_dvec3();
-    let f = f.as_dvec3();
+pub fn dvalue_fbm(p: DVec3, f: DVec3, seed: NoiseSeed) -> f64 {
+    generic::value_fbm(p.into(), f.into(), seed)
+}
+
+/// A 3D noise returning `(value, d/dx, d/dy, d/dz)` with its value in about [0, 1],
+/// so [`noised`], [`perlin3`] and [`simplex3_basis`] are interchangeable. Only the first
+/// two tile, see [`BasisKind::tiles`].
+pub type Basis = fn(Vec3, Vec3, NoiseSeed) -> Vec4;
+
+/// [`simplex3`] with the [`Basis`] signature. Simplex noise doesn't tile, so the period
+/// has to be [`NO_PERIOD`].
+///
+/// # Panics
+/// If any component of `f` is finite.
+pub fn simplex3_basis(x: Vec3, f: Vec3, seed: NoiseSeed) -> Vec4 {
+    assert!(
+        !f.x.is_finite() && !f.y.is_finite() && !f.z.is_finite(),
+        "simplex noise can't tile with period {}",
+        f
+    );
+    simplex3(x, seed)
+}
+
+/// The [`Basis`] noises by name, for the generators that take one from a file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BasisKind {
+    /// [`noised`]
+    Value,
+    /// [`perlin3`]
+    #[default]
+    Perlin,
+    /// [`simplex3_basis`]
+    Simplex,
+}
+
+impl BasisKind {
+    pub fn basis(self) -> Basis {
+        return match self {
+            BasisKind::Value => noised,
+            BasisKind::Perlin => perlin3,
+            BasisKind::Simplex => simplex3_basis,
+        };
+    }
+
+    /// Whether the basis repeats with its period, simplex noise can't.
+    pub fn tiles(self) -> bool {
+        return self != BasisKind::Simplex;
+    }
+}
+
+/// [`value_fbm`] built from any [`Basis`] instead of value noise.
+pub fn basis_fbm(p: Vec3, f: Vec3, seed: NoiseSeed, basis: Basis) -> f32 {
+    let mut p = p;
+    let f = tile_period(f);
     let mut t = 0.;
     let mut s = 1.;
     let mut c = 1.;
 
     for _ in 0..8 {
-        p += dvec3(24.0, 16.0, 34.0);
-        t += dnoised(p * s, f * s) * c;
+        p += vec3(24.0, 16.0, 34.0);
+        t += basis(p * s, f * s, seed).x * c;
         s *= 2.;
         c /= 2.;
-        // let rot = rotate(2.135532) * p.xz();
-        // p = vec3(rot.x, p.y, rot.y);
-        // let rot = rotate(1.135532) * p.yz();
-        // p = vec3(p.x, rot.x, rot.y);
     }
-    return (t / 2.7182817 * 1.75).clamp(0.0, 2.0) as f32;
+    return (t / 2.7182817 * 1.75).clamp(0.0, 2.0);
 }
 
-const ROTATE: Mat3 = mat3(
+/// Rotation and 2x scale in one, see [`OCTAVE_ROTATION`] for the pure rotation.
+pub const ROTATE: Mat3 = mat3(
     vec3(0.00, 1.60, 1.20),
     vec3(-1.60, 0.72, -0.96),
     vec3(-1.20, -0.96, 1.28),
 );
 
-fn hash33(p3: Vec3) -> Vec3 {
-    let mut p = p3;
-    p = (p * vec3(0.1031, 0.1030, 0.0973)).fract();
-    p += p.dot(p.yxz() + 33.33);
-    return ((p.xxy() + p.yxx()) * p.zyx()).fract();
-}
-
 fn remap(x: f32, a: f32, b: f32, c: f32, d: f32) -> f32 {
     return (((x - a) / (b - a)) * (d - c)) + c;
 }
 
-// TODO: tiling
-pub fn noised(x: Vec3, f: Vec3) -> Vec4 {
-    let i = x.floor();
-    let w = x.fract();
-    // cubic interpolation
-    let u = w * w * (3.0 - 2.0 * w);
-    let du = 6.0 * w * (1.0 - w);
-    let a = hash((i + vec3(0.0, 0.0, 0.0)) % f);
-    let b = hash((i + vec3(1.0, 0.0, 0.0)) % f);
-    let c = hash((i + vec3(0.0, 1.0, 0.0)) % f);
-    let d = hash((i + vec3(1.0, 1.0, 0.0)) % f);
-    let e = hash((i + vec3(0.0, 0.0, 1.0)) % f);
-    let f = hash((i + vec3(1.0, 0.0, 1.0)) % f);
-    let g = hash((i + vec3(0.0, 1.0, 1.0)) % f);
-    let h = hash((i + vec3(1.0, 1.0, 1.0)) % f);
-
-    let k0 = a;
-    let k1 = b - a;
-    let k2 = c - a;
-    let k3 = e - a;
-    let k4 = a - b - c + d;
-    let k5 = a - c - e + g;
-    let k6 = a - b - e + f;
-    let k7 = -a + b + c - d + e - f - g + h;
-
-    let deriv = du
-        * vec3(
-            k1 + k4 * u.y + k6 * u.z + k7 * u.y * u.z,
-            k2 + k5 * u.z + k4 * u.x + k7 * u.z * u.x,
-            k3 + k6 * u.x + k5 * u.y + k7 * u.x * u.y,
-        );
-    return vec4(
-        k0 + k1 * u.x
-            + k2 * u.y
-            + k3 * u.z
-            + k4 * u.x * u.y
-            + k5 * u.y * u.z
-            + k6 * u.z * u.x
-            + k7 * u.x * u.y * u.z,
-        deriv.x,
-        deriv.y,
-        deriv.z,
-    );
+/// Period actually used by the tiling noises: `f` rounded to whole lattice cells, ties
+/// to even like WGSL's `round`, at least one cell. An infinite component leaves that
+/// axis untiled.
+pub fn tile_period(f: Vec3) -> Vec3 {
+    Vec3::from_array(f.to_array().map(f32::round_ties_even)).max(Vec3::ONE)
 }
 
-pub fn dnoised(x: DVec3, f: DVec3) -> f64 {
-    let i = x.floor();
-    let w = x.fract();
-    // cubic interpolation
-    let u = w * w * (3.0 - 2.0 * w);
-    let du = 6.0 * w * (1.0 - w);
-    let a = dhash((i + dvec3(0.0, 0.0, 0.0)) % f);
-    let b = dhash((i + dvec3(1.0, 0.0, 0.0)) % f);
-    let c = dhash((i + dvec3(0.0, 1.0, 0.0)) % f);
-    let d = dhash((i + dvec3(1.0, 1.0, 0.0)) % f);
-    let e = dhash((i + dvec3(0.0, 0.0, 1.0)) % f);
-    let f = dhash((i + dvec3(1.0, 0.0, 1.0)) % f);
-    let g = dhash((i + dvec3(0.0, 1.0, 1.0)) % f);
-    let h = dhash((i + dvec3(1.0, 1.0, 1.0)) % f);
-
-    let k0 = a;
-    let k1 = b - a;
-    let k2 = c - a;
-    let k3 = e - a;
-    let k4 = a - b - c + d;
-    let k5 = a - c - e + g;
-    let k6 = a - b - e + f;
-    let k7 = -a + b + c - d + e - f - g + h;
-
-    let deriv = du
-        * dvec3(
-            k1 + k4 * u.y + k6 * u.z + k7 * u.y * u.z,
-            k2 + k5 * u.z + k4 * u.x + k7 * u.z * u.x,
-            k3 + k6 * u.x + k5 * u.y + k7 * u.x * u.y,
-        );
-    return k0
-        + k1 * u.x
-        + k2 * u.y
-        + k3 * u.z
-        + k4 * u.x * u.y
-        + k5 * u.y * u.z
-        + k6 * u.z * u.x
-        + k7 * u.x * u.y * u.z;
-}
-
-// pub fn fbmd(mut p: Vec3) -> Vec4 {
-//     let mut t = Vec4::ZERO;
-//     let mut s = 1.;
-//     let mut c = 1.;
-
-//     for i in 0..4 {
-//         p += vec3(13.123, -72., 234.23);
-//         let n = noised(p * s) * c;
-//         t.x += n.x;
-//         if i < 1 {
-//             t.y += n.y;
-//             t.z += n.z;
-//             t.w += n.w;
-//         }
-//         s *= 2.;
-//         c *= 0.5;
-
-//         let rot = rotate(2.135532) * p.xz();
-//         p = vec3(rot.x, p.y, rot.y);
-//         let rot = rotate(1.5532) * p.yz();
-//         p = vec3(p.x, rot.x, rot.y);
-//     }
-//     return t;
-// }
-
-// TODO: tiling
-pub fn worley_noise(p: Vec3, f: Vec3) -> f32 {
-    let id = p.floor();
-
-    let p = p.fract();
-
-    let mut min_dist = 10000_f32;
-    for x in [-1., 0., 1.] {
-        for y in [-1., 0., 1.] {
-            for z in [-1., 0., 1.] {
-                let offset = vec3(x, y, z);
-                let mut h = hash33((id + offset) % f) * 0.5 + 0.5;
-                h += offset;
-                let d = p - h;
-                min_dist = min_dist.min(d.dot(d));
-            }
-        }
+pub fn dtile_period(f: DVec3) -> DVec3 {
+    DVec3::from_array(f.to_array().map(f64::round_ties_even)).max(DVec3::ONE)
+}
+
+// Euclidean modulo so negative cells wrap onto the same lattice points as positive ones
+fn wrap1(i: f64, f: f64) -> f64 {
+    if f.is_finite() {
+        i - f * generic::floor64(i / f)
+    } else {
+        i
     }
+}
+
+/// Value noise with analytic derivatives `(value, d/dx, d/dy, d/dz)`, repeating every
+/// `f` units (see [`tile_period`]).
+pub fn noised(x: Vec3, f: Vec3, seed: NoiseSeed) -> Vec4 {
+    let (value, deriv) = generic::noised(x.into(), f.into(), seed);
+    return vec4(value, deriv.x, deriv.y, deriv.z);
+}
 
-    return min_dist.sqrt();
+pub fn dnoised(x: DVec3, f: DVec3, seed: NoiseSeed) -> f64 {
+    generic::noised(x.into(), f.into(), seed).0
 }
 
-pub fn wfbm(p: Vec3, f: Vec3) -> f32 {
-    let mut p = p + vec3(100.123, -12.24245, 13.414);
-    let mut t = 0.0;
+/// 4 rotated octaves of [`noised`], with the derivatives of the first one. Untiled.
+pub fn fbmd(mut p: Vec3, seed: NoiseSeed) -> Vec4 {
+    let mut t = Vec4::ZERO;
     let mut s = 1.;
     let mut c = 1.;
 
-    //TODO back to 3
-    // for _ in 0..3 {
-    for _ in 0..3 {
+    for i in 0..4 {
         p += vec3(13.123, -72., 234.23);
-        let n = worley_noise(p * s, f * s);
-        t += n * c;
-        s *= /*PI*/ 3.0;
-        c /= /*PI*/ 3.0;
-        // let rot = rotate(PI / 2.0) * p.xy();
-        // p = vec3(rot.x, rot.y, p.z);
-        // let rot = rotate(1.135532) * p.yz();
-        // p = vec3(p.x, rot.x, rot.y);
+        let n = noised(p * s, NO_PERIOD, seed) * c;
+        t.x += n.x;
+        if i < 1 {
+            t.y += n.y;
+            t.z += n.z;
+            t.w += n.w;
+        }
+        s *= 2.;
+        c *= 0.5;
+
+        let rot = rotate(2.135532) * p.xz();
+        p = vec3(rot.x, p.y, rot.y);
+        let rot = rotate(1.5532) * p.yz();
+        p = vec3(p.x, rot.x, rot.y);
     }
-    return (E - t - 1.25).clamp(0.0, 2.0);
+    return t;
+}
+
+/// F1 Worley noise, repeating every `f` units (see [`tile_period`]).
+pub fn worley_noise(p: Vec3, f: Vec3, seed: NoiseSeed) -> f32 {
+    generic::worley_noise(p.into(), f.into(), seed)
+}
+
+pub fn dworley_noise(p: DVec3, f: DVec3, seed: NoiseSeed) -> f64 {
+    generic::worley_noise(p.into(), f.into(), seed)
+}
+
+/// 3 octaves of [`worley_noise`], every octave repeats with the same period `f`.
+pub fn wfbm(p: Vec3, f: Vec3, seed: NoiseSeed) -> f32 {
+    generic::wfbm(p.into(), f.into(), seed)
+}
+
+pub fn dwfbm(p: DVec3, f: DVec3, seed: NoiseSeed) -> f64 {
+    generic::wfbm(p.into(), f.into(), seed)
 }
 
 fn rotate(a: f32) -> Mat2 {
     let (s, c) = a.sin_cos();
     mat2(vec2(c, -s), vec2(s, c))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::dvec3;
+
+    // points on both sides of the origin so negative cells get wrapped too
+    fn points() -> impl Iterator<Item = DVec3> {
+        (0..64).map(|i| {
+            let i = i as f64;
+            dvec3(i * 0.731 - 23.4, i * -0.517 + 11.9, i * 0.293 - 9.1)
+        })
+    }
+
+    // f32 positions lose about 1e-6 per unit of offset, so the f32 noises only match to
+    // within that times their slope, the f64 ones match exactly
+    fn assert_tiles(name: &str, f: DVec3, tolerance: f64, noise: impl Fn(DVec3) -> f64) {
+        for p in points() {
+            let a = noise(p);
+            for axis in [DVec3::X, DVec3::Y, DVec3::Z] {
+                for shift in [f * axis, -f * axis, 3.0 * f * axis] {
+                    let b = noise(p + shift);
+                    assert!(
+                        (a - b).abs() <= tolerance,
+                        "{} at {} = {} but {} at {}",
+                        name,
+                        p,
+                        a,
+                        b,
+                        p + shift
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lattice_noises_tile() {
+        let f = dvec3(4.0, 5.0, 7.0);
+        let f32_noise = |noise: fn(Vec3, Vec3, NoiseSeed) -> f32, seed| {
+            move |p: DVec3| noise(p.as_vec3(), f.as_vec3(), seed) as f64
+        };
+        for seed in [NoiseSeed(0), NoiseSeed(7)] {
+            assert_tiles("dvalue_fbm", f, 1e-9, |p| dvalue_fbm(p, f, seed));
+            assert_tiles("dwfbm", f, 1e-9, |p| dwfbm(p, f, seed));
+            assert_tiles("dworley_noise", f, 1e-9, |p| dworley_noise(p, f, seed));
+            assert_tiles("value_fbm", f, 1e-4, f32_noise(value_fbm, seed));
+            assert_tiles("wfbm", f, 1e-3, f32_noise(wfbm, seed));
+            assert_tiles("worley_noise", f, 1e-4, f32_noise(worley_noise, seed));
+        }
+    }
+
+    // every noise is built on these, a change here changes every baked texture
+    #[test]
+    fn hashes_are_pinned() {
+        assert_eq!(
+            generic::pcg3d([0, 0, 0]),
+            [2611992518, 2833812075, 1058359340]
+        );
+        assert_eq!(
+            generic::pcg3d([1, 2, 3]),
+            [4204755366, 1223881804, 1500469937]
+        );
+        assert_eq!(
+            generic::pcg4d([1, 2, 3, 4]),
+            [908250390, 4044648920, 3775961919, 45698095]
+        );
+        assert_eq!(NoiseSeed(0).keys(), [0; 4]);
+
+        let seed = NoiseSeed(7);
+        assert_eq!(
+            seed.hash33(vec3(1.0, -2.0, 3.0)).to_array(),
+            [0.0776059, 0.88997424, 0.55638516]
+        );
+        assert_eq!(
+            seed.hash22(vec2(-5.0, 4.0)).to_array(),
+            [0.63037664, 0.9436626]
+        );
+        assert_eq!(
+            seed.hash44(vec4(1.0, 2.0, -3.0, 4.0)).to_array(),
+            [0.66378725, 0.00811404, 0.072170794, 0.45095813]
+        );
+        let p = vec3(0.3, 1.7, -2.2);
+        assert_eq!(noised(p, vec3(4.0, 6.0, f32::INFINITY), seed).x, 0.7085382);
+        assert_eq!(worley_noise(p, Vec3::splat(4.0), seed), 0.5433954);
+        assert_eq!(
+            value_fbm(vec3(5.5, -3.25, 7.1), NO_PERIOD, seed),
+            0.76055783
+        );
+
+        // the hashes are exact, so both precisions see the same lattice values
+        let cell = V3::new(-3.0, 5.0, 11.0);
+        let single: Vec3 = generic::hash33(cell, seed).into();
+        let double = generic::hash33(V3::new(-3.0f64, 5.0, 11.0), seed);
+        assert_eq!(single.as_dvec3(), DVec3::from(double));
+    }
+
+    #[test]
+    fn period_rounds_to_whole_cells() {
+        let f = dvec3(3.6, 0.2, 6.4);
+        let seed = NoiseSeed(3);
+        assert_tiles("dworley_noise", dtile_period(f), 1e-9, |p| {
+            dworley_noise(p, f, seed)
+        });
+    }
+}
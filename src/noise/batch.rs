@@ -0,0 +1,102 @@
+// Slice versions of the baking noises. The kernels are the `generic` ones run on
+// `Lanes`, so every lane gives the same bits as calling the scalar function on its point.
+
+use bevy::prelude::Vec3;
+
+use super::{generic, F32x4, Lanes, NoiseSeed, Real, V3};
+
+fn load<T: Real<Cell = u32>, const N: usize>(
+    points: &[Vec3; N],
+    lit: impl Fn(f32) -> T,
+) -> V3<Lanes<T, N>> {
+    V3::new(
+        Lanes(points.map(|p| lit(p.x))),
+        Lanes(points.map(|p| lit(p.y))),
+        Lanes(points.map(|p| lit(p.z))),
+    )
+}
+
+// Runs `kernel` over `N` points at a time, the last chunk padded with its final point
+fn for_chunks<const N: usize>(
+    points: &[Vec3],
+    out: &mut [f32],
+    kernel: impl Fn(&[Vec3; N]) -> [f32; N],
+) {
+    assert_eq!(points.len(), out.len());
+    for (points, out) in points.chunks(N).zip(out.chunks_mut(N)) {
+        let chunk = std::array::from_fn(|i| points[i.min(points.len() - 1)]);
+        out.copy_from_slice(&kernel(&chunk)[..out.len()]);
+    }
+}
+
+/// [`noised`](super::noised)`(p, f, seed).x` for every point, four at a time.
+pub fn noised_batch(points: &[Vec3], f: Vec3, seed: NoiseSeed, out: &mut [f32]) {
+    let f = V3::from_vec3(f);
+    for_chunks(points, out, |chunk| {
+        let p: V3<F32x4> = load(chunk, |v| v);
+        generic::noised(p, f, seed).0 .0
+    });
+}
+
+/// [`wfbm`](super::wfbm) for every point, four at a time.
+pub fn wfbm_batch(points: &[Vec3], f: Vec3, seed: NoiseSeed, out: &mut [f32]) {
+    let f = V3::from_vec3(f);
+    for_chunks(points, out, |chunk| {
+        let p: V3<F32x4> = load(chunk, |v| v);
+        generic::wfbm(p, f, seed).0
+    });
+}
+
+/// [`value_fbm`](super::value_fbm) for every point, four at a time.
+pub fn value_fbm_batch(points: &[Vec3], f: Vec3, seed: NoiseSeed, out: &mut [f32]) {
+    let f = V3::from_vec3(f);
+    for_chunks(points, out, |chunk| {
+        let p: V3<F32x4> = load(chunk, |v| v);
+        generic::value_fbm(p, f, seed).0
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec3;
+
+    use super::*;
+    use crate::noise::{NoiseFn, Noised, ValueFbm, Wfbm};
+
+    // the batch kernels promise the scalar bits, not just close values
+    fn assert_bit_identical(name: &str, noise: &dyn NoiseFn) {
+        // not a multiple of the lane count, so the padded last chunk is covered too
+        let points: Vec<Vec3> = (0..203)
+            .map(|i| {
+                let i = i as f32;
+                vec3(i * 0.377 - 31.2, (i * 0.61).sin() * 9.0, i * -0.143 + 4.7)
+            })
+            .collect();
+        for period in [None, Some(vec3(4.0, 8.0, 16.0))] {
+            let mut batch = vec![0.0; points.len()];
+            noise.sample_batch(&points, period, &mut batch);
+            for (p, b) in points.iter().zip(&batch) {
+                let s = noise.sample(*p, period);
+                assert_eq!(
+                    s.to_bits(),
+                    b.to_bits(),
+                    "{} at {} with period {:?}: {} scalar, {} batched",
+                    name,
+                    p,
+                    period,
+                    s,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn batches_match_scalar_bits() {
+        for seed in [NoiseSeed(0), NoiseSeed(12345)] {
+            assert_bit_identical("noised", &Noised { seed });
+            assert_bit_identical("wfbm", &Wfbm { seed });
+            assert_bit_identical("value_fbm", &ValueFbm { seed });
+        }
+    }
+}
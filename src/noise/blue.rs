@@ -0,0 +1,178 @@
+// Void-and-cluster blue noise (Ulichney 1993). Every texel gets a unique rank, ordered
+// so that any threshold of the texture is an evenly spread point set. The energy
+// filter wraps around, so the textures tile.
+//
+// Finding each void/cluster is a scan over the whole texture, so generation is
+// quadratic in the texel count: 64² or 32³ textures take a moment, much more doesn't.
+
+use bevy::{
+    math::{vec2, vec4},
+    prelude::{Vec2, Vec4},
+};
+use rand::prelude::*;
+use rayon::prelude::*;
+
+use super::NoiseSeed;
+
+/// Added to a blue noise value every frame, see [`blue_noise_rotate`].
+pub const GOLDEN_RATIO_CONJUGATE: f64 = 0.618_033_988_749_894_9;
+
+/// The R2 low discrepancy sequence step, see [`blue_noise_offset`].
+pub const R2: Vec2 = vec2(0.754_877_7, 0.569_840_3);
+
+const SIGMA: f32 = 1.5;
+
+/// Tileable `width × height` blue noise, values evenly spread over [0, 1).
+pub fn blue_noise_2d(dims: (usize, usize), seed: NoiseSeed) -> Vec<f32> {
+    void_and_cluster([dims.0, dims.1, 1], seed)
+}
+
+/// Tileable `width × height × depth` blue noise, values evenly spread over [0, 1).
+pub fn blue_noise_3d(dims: (usize, usize, usize), seed: NoiseSeed) -> Vec<f32> {
+    void_and_cluster([dims.0, dims.1, dims.2], seed)
+}
+
+/// Four independent [`blue_noise_2d`] channels, for an `Rgba32Float` image.
+pub fn blue_noise_2d_vec4(dims: (usize, usize), seed: NoiseSeed) -> Vec<Vec4> {
+    channels(|seed| blue_noise_2d(dims, seed), seed)
+}
+
+/// Four independent [`blue_noise_3d`] channels, for an `Rgba32Float` image.
+pub fn blue_noise_3d_vec4(dims: (usize, usize, usize), seed: NoiseSeed) -> Vec<Vec4> {
+    channels(|seed| blue_noise_3d(dims, seed), seed)
+}
+
+/// Shifts a blue noise value by the golden ratio every frame. Each texel cycles through
+/// evenly spread values over time while neighbours stay decorrelated.
+pub fn blue_noise_rotate(value: f32, frame: u32) -> f32 {
+    ((value as f64 + frame as f64 * GOLDEN_RATIO_CONJUGATE).fract()) as f32
+}
+
+/// Offset in [0, 1)² to add to the lookup coordinates (in texture sizes) every frame,
+/// so a small texture doesn't show the same pattern in the same place.
+pub fn blue_noise_offset(frame: u32) -> Vec2 {
+    (R2.as_dvec2() * frame as f64).fract().as_vec2()
+}
+
+fn channels(bake: impl Fn(NoiseSeed) -> Vec<f32>, seed: NoiseSeed) -> Vec<Vec4> {
+    let [r, g, b, a] = [0, 1, 2, 3].map(|k| bake(NoiseSeed(seed.0.wrapping_add(k))));
+    (0..r.len()).map(|i| vec4(r[i], g[i], b[i], a[i])).collect()
+}
+
+#[derive(Clone)]
+struct Pattern {
+    dims: [usize; 3],
+    // Gaussian weights by offset, the radius clamped so nothing wraps onto itself twice
+    kernel: Vec<([isize; 3], f32)>,
+    energy: Vec<f32>,
+    on: Vec<bool>,
+}
+
+impl Pattern {
+    fn new(dims: [usize; 3]) -> Self {
+        let radius = dims.map(|d| ((3.0 * SIGMA).ceil() as isize).min((d as isize - 1) / 2));
+        let mut kernel = vec![];
+        for z in -radius[2]..=radius[2] {
+            for y in -radius[1]..=radius[1] {
+                for x in -radius[0]..=radius[0] {
+                    let d2 = (x * x + y * y + z * z) as f32;
+                    kernel.push(([x, y, z], (-d2 / (2.0 * SIGMA * SIGMA)).exp()));
+                }
+            }
+        }
+        let len = dims[0] * dims[1] * dims[2];
+        Self {
+            dims,
+            kernel,
+            energy: vec![0.0; len],
+            on: vec![false; len],
+        }
+    }
+
+    fn set(&mut self, i: usize, on: bool) {
+        let sign = if on { 1.0 } else { -1.0 };
+        let d = self.dims;
+        let c = [i % d[0], i / d[0] % d[1], i / (d[0] * d[1])];
+        for (offset, w) in &self.kernel {
+            let [x, y, z] =
+                [0, 1, 2].map(|k| (c[k] as isize + offset[k]).rem_euclid(d[k] as isize) as usize);
+            self.energy[x + d[0] * (y + d[1] * z)] += sign * w;
+        }
+        self.on[i] = on;
+    }
+
+    // Highest energy among the set texels, ties go to the lowest index
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    // Lowest energy among the empty texels, ties go to the lowest index
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, on: bool, better: impl Fn(f32, f32) -> bool + Sync) -> usize {
+        let pick = |a: (usize, f32), b: (usize, f32)| {
+            if better(b.1, a.1) || (b.1 == a.1 && b.0 < a.0) {
+                b
+            } else {
+                a
+            }
+        };
+        self.energy
+            .par_iter()
+            .copied()
+            .enumerate()
+            .filter(|(i, _)| self.on[*i] == on)
+            .reduce_with(pick)
+            .expect("blue noise pattern is all on or all off")
+            .0
+    }
+}
+
+fn void_and_cluster(dims: [usize; 3], seed: NoiseSeed) -> Vec<f32> {
+    let len = dims[0] * dims[1] * dims[2];
+    let mut rng = StdRng::seed_from_u64(seed.0 as u64);
+    let mut pattern = Pattern::new(dims);
+
+    // a tenth of the texels at random as the starting point
+    let initial = (len / 10).max(1);
+    let mut placed = 0;
+    while placed < initial {
+        let i = rng.gen_range(0..len);
+        if !pattern.on[i] {
+            pattern.set(i, true);
+            placed += 1;
+        }
+    }
+
+    // move points from clusters into voids until the pattern is evenly spread
+    for _ in 0..len {
+        let cluster = pattern.tightest_cluster();
+        pattern.set(cluster, false);
+        let void = pattern.largest_void();
+        pattern.set(void, true);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; len];
+    let mut removing = pattern.clone();
+    for r in (0..initial).rev() {
+        let cluster = removing.tightest_cluster();
+        removing.set(cluster, false);
+        rank[cluster] = r;
+    }
+    // the largest void among the empty texels is also the tightest cluster of empty
+    // texels, so this one loop covers both of the remaining phases
+    for r in initial..len {
+        let void = pattern.largest_void();
+        pattern.set(void, true);
+        rank[void] = r;
+    }
+
+    rank.iter()
+        .map(|&r| (r as f32 + 0.5) / len as f32)
+        .collect()
+}
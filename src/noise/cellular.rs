@@ -0,0 +1,255 @@
+// Worley/cellular noise with a choice of output, distance metric and jitter.
+//
+// Unlike `worley_noise`, feature points sit at `0.5 + (hash - 0.5) * jitter` inside
+// their cell, so a jitter of 0 gives a regular grid and 1 the usual random points.
+// Jitter is clamped to [0, 1], further out a feature point could hide from the search.
+
+use bevy::prelude::{Reflect, Vec2, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::{wrap1, NoiseFn, NoiseSeed, NO_PERIOD};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect, Serialize, Deserialize)]
+pub enum CellularReturn {
+    /// Distance to the closest feature point
+    #[default]
+    F1,
+    /// Distance to the second closest feature point
+    F2,
+    /// `F2 - F1`, zero along the cell borders
+    F2MinusF1,
+    /// A random value in [0, 1) that's constant over each cell
+    CellId,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect, Serialize, Deserialize)]
+pub enum DistanceMetric {
+    #[default]
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CellularSettings {
+    pub output: CellularReturn,
+    pub metric: DistanceMetric,
+    /// How far feature points stray from their cell centre, clamped to [0, 1]
+    pub jitter: f32,
+}
+
+impl Default for CellularSettings {
+    fn default() -> Self {
+        Self {
+            output: CellularReturn::F1,
+            metric: DistanceMetric::Euclidean,
+            jitter: 1.0,
+        }
+    }
+}
+
+/// 2D cellular noise, repeats every `f` units.
+pub fn cellular2(p: Vec2, f: Vec2, settings: CellularSettings, seed: NoiseSeed) -> f32 {
+    cellular(
+        p.to_array(),
+        f.round().max(Vec2::ONE).to_array(),
+        settings,
+        seed,
+        |seed, i| seed.hash22(Vec2::from(i)).to_array(),
+    )
+}
+
+/// 3D cellular noise, repeats every `f` units.
+pub fn cellular3(p: Vec3, f: Vec3, settings: CellularSettings, seed: NoiseSeed) -> f32 {
+    cellular(
+        p.to_array(),
+        f.round().max(Vec3::ONE).to_array(),
+        settings,
+        seed,
+        |seed, i| seed.hash33(Vec3::from(i)).to_array(),
+    )
+}
+
+/// 4D cellular noise, repeats every `f` units.
+pub fn cellular4(p: Vec4, f: Vec4, settings: CellularSettings, seed: NoiseSeed) -> f32 {
+    cellular(
+        p.to_array(),
+        f.round().max(Vec4::ONE).to_array(),
+        settings,
+        seed,
+        |seed, i| seed.hash44(Vec4::from(i)).to_array(),
+    )
+}
+
+fn cellular<const N: usize>(
+    x: [f32; N],
+    period: [f32; N],
+    settings: CellularSettings,
+    seed: NoiseSeed,
+    hash: impl Fn(NoiseSeed, [f32; N]) -> [f32; N],
+) -> f32 {
+    let i = x.map(f32::floor);
+    let w: [f32; N] = std::array::from_fn(|k| x[k] - i[k]);
+    let jitter = settings.jitter.clamp(0.0, 1.0);
+
+    // F2 can come from two cells away, F1 and the cell id only need the neighbours
+    let reach = match settings.output {
+        CellularReturn::F1 | CellularReturn::CellId => 1,
+        CellularReturn::F2 | CellularReturn::F2MinusF1 => 2,
+    };
+    let span: i32 = 2 * reach + 1;
+
+    let mut f1 = f32::INFINITY;
+    let mut f2 = f32::INFINITY;
+    let mut closest = [0.0; N];
+    for n in 0..span.pow(N as u32) {
+        let offset: [f32; N] =
+            std::array::from_fn(|k| ((n / span.pow(k as u32)) % span - reach) as f32);
+        let cell: [f32; N] =
+            std::array::from_fn(|k| wrap1((i[k] + offset[k]) as f64, period[k] as f64) as f32);
+        let h = hash(seed, cell);
+        let d: [f32; N] = std::array::from_fn(|k| offset[k] + 0.5 + (h[k] - 0.5) * jitter - w[k]);
+        let dist = match settings.metric {
+            DistanceMetric::Euclidean => d.iter().map(|d| d * d).sum::<f32>().sqrt(),
+            DistanceMetric::Manhattan => d.iter().map(|d| d.abs()).sum(),
+            DistanceMetric::Chebyshev => d.iter().fold(0.0, |m, d| d.abs().max(m)),
+        };
+        if dist < f1 {
+            f2 = f1;
+            f1 = dist;
+            closest = cell;
+        } else if dist < f2 {
+            f2 = dist;
+        }
+    }
+
+    match settings.output {
+        CellularReturn::F1 => f1,
+        CellularReturn::F2 => f2,
+        CellularReturn::F2MinusF1 => f2 - f1,
+        // hashed with another seed so it doesn't follow the feature point
+        CellularReturn::CellId => hash(seed.salted(), closest)[0],
+    }
+}
+
+/// [`cellular3`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Cellular {
+    pub settings: CellularSettings,
+    pub seed: NoiseSeed,
+}
+
+impl NoiseFn for Cellular {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        cellular3(p, period.unwrap_or(NO_PERIOD), self.settings, self.seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(output: CellularReturn, metric: DistanceMetric, jitter: f32) -> CellularSettings {
+        CellularSettings {
+            output,
+            metric,
+            jitter,
+        }
+    }
+
+    fn points() -> impl Iterator<Item = Vec3> {
+        (0..200).map(|i| Vec3::new(i as f32 * 0.173, i as f32 * 0.291, i as f32 * 0.057) - 9.0)
+    }
+
+    #[test]
+    fn f1_is_at_most_f2() {
+        let seed = NoiseSeed(3);
+        for metric in [
+            DistanceMetric::Euclidean,
+            DistanceMetric::Manhattan,
+            DistanceMetric::Chebyshev,
+        ] {
+            let f1 = settings(CellularReturn::F1, metric, 1.0);
+            let f2 = settings(CellularReturn::F2, metric, 1.0);
+            for p in points() {
+                let (f1, f2) = (
+                    cellular3(p, NO_PERIOD, f1, seed),
+                    cellular3(p, NO_PERIOD, f2, seed),
+                );
+                assert!(f1 <= f2, "{:?} at {}: F1 {} > F2 {}", metric, p, f1, f2);
+            }
+        }
+    }
+
+    #[test]
+    fn f1_is_zero_at_feature_points() {
+        let seed = NoiseSeed(3);
+        for jitter in [0.0, 0.5, 1.0] {
+            let f1 = settings(CellularReturn::F1, DistanceMetric::Euclidean, jitter);
+            for cell in [
+                Vec3::ZERO,
+                Vec3::new(3.0, -2.0, 5.0),
+                Vec3::new(-7.0, 1.0, 4.0),
+            ] {
+                let feature = cell + 0.5 + (seed.hash33(cell) - 0.5) * jitter;
+                let d = cellular3(feature, NO_PERIOD, f1, seed);
+                assert!(d < 1e-5, "F1 at the feature point of {}: {}", cell, d);
+            }
+        }
+    }
+
+    #[test]
+    fn jitter_is_clamped() {
+        let seed = NoiseSeed(3);
+        let f2 = |jitter| settings(CellularReturn::F2, DistanceMetric::Euclidean, jitter);
+        for p in points() {
+            assert_eq!(
+                cellular3(p, NO_PERIOD, f2(4.0), seed),
+                cellular3(p, NO_PERIOD, f2(1.0), seed)
+            );
+            assert_eq!(
+                cellular3(p, NO_PERIOD, f2(-1.0), seed),
+                cellular3(p, NO_PERIOD, f2(0.0), seed)
+            );
+        }
+    }
+
+    #[test]
+    fn cell_ids_are_stable() {
+        let seed = NoiseSeed(3);
+        let id = |p, f, jitter| {
+            let id = settings(CellularReturn::CellId, DistanceMetric::Euclidean, jitter);
+            cellular3(p, f, id, seed)
+        };
+        // without jitter the cells are the unit cubes, constant inside and tiling with
+        // the period
+        let period = Vec3::new(4.0, 5.0, 3.0);
+        for cell in [
+            Vec3::ZERO,
+            Vec3::new(1.0, 2.0, 0.0),
+            Vec3::new(3.0, 4.0, 2.0),
+        ] {
+            let centre = id(cell + 0.5, period, 0.0);
+            for offset in [Vec3::splat(0.1), Vec3::new(0.9, 0.2, 0.6), Vec3::splat(0.8)] {
+                assert_eq!(id(cell + offset, period, 0.0), centre);
+                assert_eq!(id(cell + offset + period, period, 0.0), centre);
+            }
+        }
+        let ids: Vec<f32> = (0..8)
+            .map(|x| id(Vec3::new(x as f32 + 0.5, 0.5, 0.5), NO_PERIOD, 0.0))
+            .collect();
+        assert!(
+            ids.windows(2).all(|w| w[0] != w[1]),
+            "neighbouring cells share ids {:?}",
+            ids
+        );
+        // with jitter it's still constant near each feature point
+        for cell in [Vec3::ZERO, Vec3::new(3.0, -2.0, 5.0)] {
+            let feature = cell + 0.5 + (seed.hash33(cell) - 0.5);
+            let at = id(feature, NO_PERIOD, 1.0);
+            assert_eq!(id(feature + Vec3::splat(0.01), NO_PERIOD, 1.0), at);
+            assert_eq!(id(feature - Vec3::splat(0.01), NO_PERIOD, 1.0), at);
+        }
+    }
+}
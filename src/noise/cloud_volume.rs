@@ -0,0 +1,58 @@
+// The Perlin-Worley volumes raymarched clouds are built from: a base shape volume
+// (Perlin-Worley plus three Worley fBm frequencies) and a smaller detail volume used
+// to erode its edges. Both tile every `f` units when baked over exactly `f` units.
+
+use bevy::prelude::{Vec3, Vec4};
+
+use super::{perlin3, remap, tile_period, worley_noise, Basis, NoiseSeed};
+
+/// Three octaves of inverted [`worley_noise`] at `f`, `2f` and `4f`, in [0, 1].
+pub fn worley_fbm(p: Vec3, f: Vec3, seed: NoiseSeed) -> f32 {
+    let f = tile_period(f);
+    let cells = |s: f32| 1.0 - worley_noise(p * s, f * s, seed).min(1.0);
+    cells(1.0) * 0.625 + cells(2.0) * 0.25 + cells(4.0) * 0.125
+}
+
+/// Seven octaves of `basis`, normalised to about [0, 1].
+pub fn shape_fbm(p: Vec3, f: Vec3, seed: NoiseSeed, basis: Basis) -> f32 {
+    let f = tile_period(f);
+    let mut t = 0.;
+    let mut s = 1.;
+    let mut c = 1.;
+    let mut total = 0.;
+
+    for _ in 0..7 {
+        t += basis(p * s, f * s, seed).x * c;
+        total += c;
+        s *= 2.;
+        c *= 0.5;
+    }
+    return t / total;
+}
+
+/// [`shape_fbm`] of [`perlin3`].
+pub fn perlin_fbm(p: Vec3, f: Vec3, seed: NoiseSeed) -> f32 {
+    return shape_fbm(p, f, seed, perlin3);
+}
+
+/// [`shape_fbm`] of `basis` remapped by Worley fBm, billowy blobs with connected wispy
+/// edges. Perlin-Worley with [`perlin3`] as the basis.
+pub fn perlin_worley(p: Vec3, f: Vec3, seed: NoiseSeed, basis: Basis) -> f32 {
+    let fbm = shape_fbm(p, f, seed, basis);
+    let worley = worley_fbm(p, f, seed);
+    return remap(fbm, worley - 1.0, 1.0, 0.0, 1.0).clamp(0.0, 1.0);
+}
+
+/// Texel of the base shape volume: `(perlin_worley, worley_fbm at f, 2f and 4f)`.
+pub fn cloud_shape(p: Vec3, f: Vec3, seed: NoiseSeed, basis: Basis) -> Vec4 {
+    let f = tile_period(f);
+    let w = |s: f32| worley_fbm(p * s, f * s, seed);
+    Vec4::new(perlin_worley(p, f, seed, basis), w(1.0), w(2.0), w(4.0))
+}
+
+/// Texel of the detail volume: [`worley_fbm`] at `f`, `2f` and `4f`, alpha unused.
+pub fn cloud_detail(p: Vec3, f: Vec3, seed: NoiseSeed) -> Vec4 {
+    let f = tile_period(f);
+    let w = |s: f32| worley_fbm(p * s, f * s, seed);
+    Vec4::new(w(1.0), w(2.0), w(4.0), 1.0)
+}
@@ -0,0 +1,73 @@
+use bevy::{math::vec3, prelude::Vec3};
+
+use super::{noised, NoiseFn, NoiseSeed};
+
+// Decorrelates the three components of the vector potential
+const POTENTIAL_OFFSETS: [Vec3; 2] = [
+    Vec3::new(31.416, -47.853, 12.793),
+    Vec3::new(-19.637, 27.171, 83.269),
+];
+
+/// Divergence free 3D flow, the curl of a potential made from three [`noised`] fields.
+/// Repeats every `f` units like `noised`.
+pub fn curl_noise(p: Vec3, f: Vec3, seed: NoiseSeed) -> Vec3 {
+    let a = noised(p, f, seed);
+    let b = noised(p + POTENTIAL_OFFSETS[0], f, seed);
+    let c = noised(p + POTENTIAL_OFFSETS[1], f, seed);
+    // .yzw hold d/dx, d/dy and d/dz
+    vec3(c.z - b.w, a.w - c.y, b.y - a.z)
+}
+
+/// [`curl_noise`] built from any [`NoiseFn`], using its `sample_d`.
+pub fn curl(potential: &impl NoiseFn, p: Vec3, period: Option<Vec3>) -> Vec3 {
+    let a = potential.sample_d(p, period);
+    let b = potential.sample_d(p + POTENTIAL_OFFSETS[0], period);
+    let c = potential.sample_d(p + POTENTIAL_OFFSETS[1], period);
+    vec3(c.z - b.w, a.w - c.y, b.y - a.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::{Perlin, NO_PERIOD};
+
+    // central differences of the three components, each along its own axis
+    fn divergence(field: impl Fn(Vec3) -> Vec3, p: Vec3) -> (f32, f32) {
+        const H: f32 = 1e-3;
+        let terms = [Vec3::X, Vec3::Y, Vec3::Z]
+            .map(|axis| (field(p + axis * H) - field(p - axis * H)).dot(axis) / (2.0 * H));
+        (terms.iter().sum(), terms.iter().map(|t| t.abs()).sum())
+    }
+
+    #[test]
+    fn curl_is_divergence_free() {
+        let seed = NoiseSeed(8);
+        let period = Vec3::new(4.0, 5.0, 6.0);
+        let perlin = Perlin { seed };
+        for i in 0..100 {
+            let p = vec3(i as f32 * 0.173, i as f32 * 0.291, i as f32 * 0.057) - 7.0;
+            for (name, (div, size)) in [
+                (
+                    "curl_noise",
+                    divergence(|p| curl_noise(p, NO_PERIOD, seed), p),
+                ),
+                (
+                    "tiling curl_noise",
+                    divergence(|p| curl_noise(p, period, seed), p),
+                ),
+                ("curl of Perlin", divergence(|p| curl(&perlin, p, None), p)),
+            ] {
+                // f32 rounding leaves about a thousandth, a field that isn't divergence
+                // free is off by as much as its terms
+                assert!(
+                    div.abs() < 1e-2 * size.max(1.0),
+                    "{} at {}: divergence {} of terms summing to {}",
+                    name,
+                    p,
+                    div,
+                    size
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use bevy::{
+    math::{mat3, vec3},
+    prelude::{Mat3, Reflect, Vec3},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{tile_period, NoiseFn};
+
+/// Rotation (no scaling) between octaves, [`ROTATE`](super::ROTATE) divided by 2.
+pub const OCTAVE_ROTATION: Mat3 = mat3(
+    vec3(0.00, 0.80, 0.60),
+    vec3(-0.80, 0.36, -0.48),
+    vec3(-0.60, -0.48, 0.64),
+);
+
+/// How each octave is shaped before it's summed, `n` being the base noise in [0, 1].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect, Serialize, Deserialize)]
+pub enum FractalKind {
+    /// `n`
+    #[default]
+    Fbm,
+    /// `(1 - |2n - 1|)²`, weighted by the previous octave so ridges stay sharp
+    Ridged,
+    /// `sqrt((2n - 1)² + 0.05)`, turbulence with the creases rounded off
+    Billow,
+    /// `|2n - 1|`
+    Turbulence,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FractalSettings {
+    pub octaves: u32,
+    /// Frequency multiplier between octaves
+    pub lacunarity: f32,
+    /// Amplitude multiplier between octaves
+    pub gain: f32,
+    /// Added to the sample position before every octave
+    pub offset: Vec3,
+    /// Applied to the sample position after every octave, anything but the identity
+    /// stops the result from tiling
+    pub rotation: Mat3,
+    pub kind: FractalKind,
+}
+
+impl Default for FractalSettings {
+    fn default() -> Self {
+        Self {
+            octaves: 8,
+            lacunarity: 2.0,
+            gain: 0.5,
+            offset: Vec3::ZERO,
+            rotation: Mat3::IDENTITY,
+            kind: FractalKind::Fbm,
+        }
+    }
+}
+
+impl FractalSettings {
+    /// The octaves of [`value_fbm`](super::value_fbm)
+    pub fn value_fbm() -> Self {
+        Self {
+            offset: vec3(24.0, 16.0, 34.0),
+            ..Self::default()
+        }
+    }
+
+    /// The octaves of [`wfbm`](super::wfbm)
+    pub fn wfbm() -> Self {
+        Self {
+            octaves: 3,
+            lacunarity: 3.0,
+            gain: 1.0 / 3.0,
+            offset: vec3(13.123, -72., 234.23),
+            ..Self::default()
+        }
+    }
+
+    pub fn octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    pub fn lacunarity(mut self, lacunarity: f32) -> Self {
+        self.lacunarity = lacunarity;
+        self
+    }
+
+    pub fn gain(mut self, gain: f32) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    pub fn offset(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn rotation(mut self, rotation: Mat3) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn kind(mut self, kind: FractalKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Sum of the octave amplitudes, the largest value an fbm of a [0, 1] noise reaches
+    pub fn amplitude(&self) -> f32 {
+        (0..self.octaves).map(|i| self.gain.powi(i as i32)).sum()
+    }
+}
+
+/// Octaves of any [`NoiseFn`] summed according to [`FractalSettings`].
+///
+/// Periods are rounded once and scaled by the lacunarity, so with an integer
+/// lacunarity and no rotation every octave tiles with the same period.
+#[derive(Clone, Debug)]
+pub struct Fractal<N> {
+    pub base: N,
+    pub settings: FractalSettings,
+}
+
+impl<N: NoiseFn> Fractal<N> {
+    pub fn new(base: N, settings: FractalSettings) -> Self {
+        Self { base, settings }
+    }
+}
+
+impl<N: NoiseFn> NoiseFn for Fractal<N> {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        let settings = &self.settings;
+        let period = period.map(tile_period);
+        let mut p = p;
+        let mut t = 0.;
+        let mut s = 1.;
+        let mut c = 1.;
+        let mut weight = 1.0_f32;
+
+        for _ in 0..settings.octaves {
+            p += settings.offset;
+            let n = self.base.sample(p * s, period.map(|f| f * s));
+            let signed = 2.0 * n - 1.0;
+            let octave = match settings.kind {
+                FractalKind::Fbm => n,
+                FractalKind::Ridged => {
+                    let ridge = (1.0 - signed.abs()).powi(2) * weight;
+                    weight = (ridge * 2.0).clamp(0.0, 1.0);
+                    ridge
+                }
+                FractalKind::Billow => (signed * signed + 0.05).sqrt(),
+                FractalKind::Turbulence => signed.abs(),
+            };
+            t += octave * c;
+            s *= settings.lacunarity;
+            c *= settings.gain;
+            p = settings.rotation * p;
+        }
+        t
+    }
+}
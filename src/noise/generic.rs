@@ -0,0 +1,546 @@
+// The lattice noises written once over `Real`, so the same code runs in f32, f64 or
+// on `Lanes`. The plain `noise::*` functions are thin wrappers around these. The hashes
+// are integer hashes of the lattice cell with 24 bit results, run on `u32` lanes next to
+// the float ones, so every precision (and the WGSL) sees exactly the same lattice values
+// and the f32 and f64 noises are the same field rounded differently.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use bevy::{
+    math::{dvec3, vec3, DVec3},
+    prelude::Vec3,
+};
+
+use super::{wrap1, NoiseSeed};
+
+pub trait Real:
+    Copy
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    /// A constant, written as an f64 literal and rounded to the precision of `Self`
+    fn lit(x: f64) -> Self;
+    fn floor(self) -> Self;
+    /// Ties to even, like WGSL's `round`
+    fn round(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    /// Euclidean modulo by a whole number period, always computed in f64 like `wrap1`
+    fn wrap(self, period: Self) -> Self;
+    /// [`Real::wrap`] of a whole number at most one period outside `[0, period)`, a
+    /// lattice cell one step from a wrapped one, without the division
+    fn wrap_near(self, period: Self) -> Self;
+
+    /// What the integer hashes run on, `u32` or `Lanes` of them
+    type Cell: Word;
+    /// A whole number as the lattice cell it's hashed as, negative cells wrap around
+    /// like `i32 as u32`
+    fn cell(self) -> Self::Cell;
+    /// The top 24 bits of a hash in [0, 1), exact in f32
+    fn unit(h: Self::Cell) -> Self;
+
+    fn fract(self) -> Self {
+        self - self.floor()
+    }
+
+    fn clamp(self, min: Self, max: Self) -> Self {
+        self.max(min).min(max)
+    }
+}
+
+impl Real for f32 {
+    fn lit(x: f64) -> Self {
+        x as f32
+    }
+    fn floor(self) -> Self {
+        floor32(self)
+    }
+    fn round(self) -> Self {
+        f32::round_ties_even(self)
+    }
+    fn sqrt(self) -> Self {
+        f32::sqrt(self)
+    }
+    fn min(self, other: Self) -> Self {
+        f32::min(self, other)
+    }
+    fn max(self, other: Self) -> Self {
+        f32::max(self, other)
+    }
+    fn wrap(self, period: Self) -> Self {
+        wrap1(self as f64, period as f64) as f32
+    }
+    fn wrap_near(self, period: Self) -> Self {
+        if self >= period {
+            self - period
+        } else if self < 0.0 && period.is_finite() {
+            self + period
+        } else {
+            self
+        }
+    }
+
+    type Cell = u32;
+    fn cell(self) -> u32 {
+        self as i32 as u32
+    }
+    fn unit(h: u32) -> Self {
+        (h >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
+
+impl Real for f64 {
+    fn lit(x: f64) -> Self {
+        x
+    }
+    fn floor(self) -> Self {
+        floor64(self)
+    }
+    fn round(self) -> Self {
+        f64::round_ties_even(self)
+    }
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+    fn min(self, other: Self) -> Self {
+        f64::min(self, other)
+    }
+    fn max(self, other: Self) -> Self {
+        f64::max(self, other)
+    }
+    fn wrap(self, period: Self) -> Self {
+        wrap1(self, period)
+    }
+    fn wrap_near(self, period: Self) -> Self {
+        if self >= period {
+            self - period
+        } else if self < 0.0 && period.is_finite() {
+            self + period
+        } else {
+            self
+        }
+    }
+
+    type Cell = u32;
+    fn cell(self) -> u32 {
+        self as i32 as u32
+    }
+    fn unit(h: u32) -> Self {
+        (h >> 8) as f64 / (1u32 << 24) as f64
+    }
+}
+
+/// Wrapping `u32` arithmetic, on one word or `Lanes` of them.
+pub trait Word: Copy {
+    fn word(x: u32) -> Self;
+    fn add(self, other: Self) -> Self;
+    fn mul(self, other: Self) -> Self;
+    /// `self ^ (self >> 16)`
+    fn xorshift(self) -> Self;
+}
+
+impl Word for u32 {
+    fn word(x: u32) -> Self {
+        x
+    }
+    fn add(self, other: Self) -> Self {
+        self.wrapping_add(other)
+    }
+    fn mul(self, other: Self) -> Self {
+        self.wrapping_mul(other)
+    }
+    fn xorshift(self) -> Self {
+        self ^ (self >> 16)
+    }
+}
+
+// `floor` without the libm call, so it vectorises on `Lanes`. Past 2^23 every f32 is
+// already whole. Gives +0 for -0, which nothing here can tell apart.
+fn floor32(x: f32) -> f32 {
+    let t = x as i32 as f32;
+    let t = if t > x { t - 1.0 } else { t };
+    if x.abs() < 8388608.0 {
+        t
+    } else {
+        x
+    }
+}
+
+// `floor32` for f64, whole past 2^52
+pub(super) fn floor64(x: f64) -> f64 {
+    let t = x as i64 as f64;
+    let t = if t > x { t - 1.0 } else { t };
+    if x.abs() < 4503599627370496.0 {
+        t
+    } else {
+        x
+    }
+}
+
+/// A 3D point of any `Real`, converts to and from `Vec3`/`DVec3`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct V3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+impl<T: Real> V3<T> {
+    pub fn new(x: T, y: T, z: T) -> Self {
+        V3 { x, y, z }
+    }
+
+    pub fn splat(v: T) -> Self {
+        V3 { x: v, y: v, z: v }
+    }
+
+    pub fn lit(x: f64, y: f64, z: f64) -> Self {
+        V3::new(T::lit(x), T::lit(y), T::lit(z))
+    }
+
+    pub fn lit_array(a: [f64; 3]) -> Self {
+        V3::lit(a[0], a[1], a[2])
+    }
+
+    /// An f32 vector, exact in every precision
+    pub fn from_vec3(v: Vec3) -> Self {
+        V3::lit(v.x as f64, v.y as f64, v.z as f64)
+    }
+
+    pub fn map(self, f: impl Fn(T) -> T) -> Self {
+        V3::new(f(self.x), f(self.y), f(self.z))
+    }
+
+    pub fn zip(self, o: Self, f: impl Fn(T, T) -> T) -> Self {
+        V3::new(f(self.x, o.x), f(self.y, o.y), f(self.z, o.z))
+    }
+
+    pub fn scale(self, s: T) -> Self {
+        self.map(|v| v * s)
+    }
+
+    pub fn dot(self, o: Self) -> T {
+        self.x * o.x + self.y * o.y + self.z * o.z
+    }
+
+    pub fn floor(self) -> Self {
+        self.map(T::floor)
+    }
+
+    pub fn fract(self) -> Self {
+        self.map(T::fract)
+    }
+
+    fn wrap(self, period: Self) -> Self {
+        self.zip(period, T::wrap)
+    }
+    fn wrap_near(self, period: Self) -> Self {
+        self.zip(period, T::wrap_near)
+    }
+}
+
+impl<T: Real> Add for V3<T> {
+    type Output = Self;
+    fn add(self, o: Self) -> Self {
+        self.zip(o, Add::add)
+    }
+}
+
+impl<T: Real> Sub for V3<T> {
+    type Output = Self;
+    fn sub(self, o: Self) -> Self {
+        self.zip(o, Sub::sub)
+    }
+}
+
+impl<T: Real> Mul for V3<T> {
+    type Output = Self;
+    fn mul(self, o: Self) -> Self {
+        self.zip(o, Mul::mul)
+    }
+}
+
+impl From<Vec3> for V3<f32> {
+    fn from(v: Vec3) -> Self {
+        V3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<V3<f32>> for Vec3 {
+    fn from(v: V3<f32>) -> Self {
+        vec3(v.x, v.y, v.z)
+    }
+}
+
+impl From<DVec3> for V3<f64> {
+    fn from(v: DVec3) -> Self {
+        V3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<V3<f64>> for DVec3 {
+    fn from(v: V3<f64>) -> Self {
+        dvec3(v.x, v.y, v.z)
+    }
+}
+
+// The constants below are shared with the generated WGSL, see `wgsl.rs`
+pub const WFBM_OCTAVES: u32 = 3;
+pub const WFBM_START: [f64; 3] = [100.123, -12.24245, 13.414];
+pub const WFBM_OFFSET: [f64; 3] = [13.123, -72., 234.23];
+pub const WFBM_LACUNARITY: f64 = 3.0;
+/// wfbm returns `E - t - WFBM_BIAS`
+pub const WFBM_BIAS: f64 = 1.25;
+pub const VALUE_FBM_OCTAVES: u32 = 8;
+pub const VALUE_FBM_OFFSET: [f64; 3] = [24.0, 16.0, 34.0];
+/// value_fbm returns `t / VALUE_FBM_DIV * VALUE_FBM_MUL`
+pub const VALUE_FBM_DIV: f64 = 2.7182817;
+pub const VALUE_FBM_MUL: f64 = 1.75;
+
+/// [`tile_period`](super::tile_period) in any precision
+pub fn tile_period<T: Real>(f: V3<T>) -> V3<T> {
+    f.map(|v| v.round().max(T::lit(1.0)))
+}
+
+/// The PCG-style 3D hash from Jarzynski and Olano, "Hash Functions for GPU Rendering"
+pub fn pcg3d<W: Word>(v: [W; 3]) -> [W; 3] {
+    let [mut x, mut y, mut z] = v.map(|v| v.mul(W::word(1664525)).add(W::word(1013904223)));
+    x = x.add(y.mul(z));
+    y = y.add(z.mul(x));
+    z = z.add(x.mul(y));
+    [x, y, z] = [x.xorshift(), y.xorshift(), z.xorshift()];
+    x = x.add(y.mul(z));
+    y = y.add(z.mul(x));
+    z = z.add(x.mul(y));
+    return [x, y, z];
+}
+
+/// The 4D version of [`pcg3d`]
+pub fn pcg4d<W: Word>(v: [W; 4]) -> [W; 4] {
+    let [mut x, mut y, mut z, mut w] = v.map(|v| v.mul(W::word(1664525)).add(W::word(1013904223)));
+    x = x.add(y.mul(w));
+    y = y.add(z.mul(x));
+    z = z.add(x.mul(y));
+    w = w.add(y.mul(z));
+    [x, y, z, w] = [x.xorshift(), y.xorshift(), z.xorshift(), w.xorshift()];
+    x = x.add(y.mul(w));
+    y = y.add(z.mul(x));
+    z = z.add(x.mul(y));
+    w = w.add(y.mul(z));
+    return [x, y, z, w];
+}
+
+/// Three values in [0, 1) for the lattice cell `p`, which has to be whole numbers. The
+/// cell is shifted by the seed's keys first, what the WGSL runs.
+pub fn hash33<T: Real>(p: V3<T>, seed: NoiseSeed) -> V3<T> {
+    let keys = seed.keys().map(T::Cell::word);
+    let h = pcg3d([
+        p.x.cell().add(keys[0]),
+        p.y.cell().add(keys[1]),
+        p.z.cell().add(keys[2]),
+    ]);
+    return V3::new(T::unit(h[0]), T::unit(h[1]), T::unit(h[2]));
+}
+
+/// The first value of [`hash33`], the corner values of the value noises
+pub fn hash<T: Real>(p: V3<T>, seed: NoiseSeed) -> T {
+    hash33(p, seed).x
+}
+
+/// See [`value_noise`](super::value_noise)
+pub fn value_noise<T: Real>(x: V3<T>, seed: NoiseSeed) -> T {
+    let i = x.floor();
+    let w = x.fract();
+    // cubic interpolation
+    let u = w * w * (V3::lit(3.0, 3.0, 3.0) - w.scale(T::lit(2.0)));
+    let corner = |c: V3<T>| hash(i + c, seed);
+    let (k0, k1, k2, k3, k4, k5, k6, k7) = corner_coefficients(corner);
+
+    return k0
+        + k1 * u.x
+        + k2 * u.y
+        + k3 * u.z
+        + k4 * u.x * u.y
+        + k5 * u.y * u.z
+        + k6 * u.z * u.x
+        + k7 * u.x * u.y * u.z;
+}
+
+/// See [`noised`](super::noised), returns the value and its gradient
+pub fn noised<T: Real>(x: V3<T>, f: V3<T>, seed: NoiseSeed) -> (T, V3<T>) {
+    let period = tile_period(f);
+    let i = x.floor();
+    let w = x.fract();
+    // cubic interpolation
+    let u = w * w * (V3::lit(3.0, 3.0, 3.0) - w.scale(T::lit(2.0)));
+    let du = w.scale(T::lit(6.0)) * (V3::lit(1.0, 1.0, 1.0) - w);
+    let cell = i.wrap(period);
+    let corner = |c: V3<T>| hash((cell + c).wrap_near(period), seed);
+    let (k0, k1, k2, k3, k4, k5, k6, k7) = corner_coefficients(corner);
+
+    let deriv = du
+        * V3::new(
+            k1 + k4 * u.y + k6 * u.z + k7 * u.y * u.z,
+            k2 + k5 * u.z + k4 * u.x + k7 * u.z * u.x,
+            k3 + k6 * u.x + k5 * u.y + k7 * u.x * u.y,
+        );
+    let value = k0
+        + k1 * u.x
+        + k2 * u.y
+        + k3 * u.z
+        + k4 * u.x * u.y
+        + k5 * u.y * u.z
+        + k6 * u.z * u.x
+        + k7 * u.x * u.y * u.z;
+    return (value, deriv);
+}
+
+fn corner_coefficients<T: Real>(corner: impl Fn(V3<T>) -> T) -> (T, T, T, T, T, T, T, T) {
+    let a = corner(V3::lit(0.0, 0.0, 0.0));
+    let b = corner(V3::lit(1.0, 0.0, 0.0));
+    let c = corner(V3::lit(0.0, 1.0, 0.0));
+    let d = corner(V3::lit(1.0, 1.0, 0.0));
+    let e = corner(V3::lit(0.0, 0.0, 1.0));
+    let f = corner(V3::lit(1.0, 0.0, 1.0));
+    let g = corner(V3::lit(0.0, 1.0, 1.0));
+    let h = corner(V3::lit(1.0, 1.0, 1.0));
+
+    return (
+        a,
+        b - a,
+        c - a,
+        e - a,
+        a - b - c + d,
+        a - c - e + g,
+        a - b - e + f,
+        -a + b + c - d + e - f - g + h,
+    );
+}
+
+/// See [`worley_noise`](super::worley_noise)
+pub fn worley_noise<T: Real>(p: V3<T>, f: V3<T>, seed: NoiseSeed) -> T {
+    let period = tile_period(f);
+    let cell = p.floor().wrap(period);
+
+    let p = p.fract();
+
+    let mut min_dist = T::lit(10000.0);
+    for x in [-1., 0., 1.] {
+        for y in [-1., 0., 1.] {
+            for z in [-1., 0., 1.] {
+                let offset = V3::lit(x, y, z);
+                let mut h = hash33((cell + offset).wrap_near(period), seed)
+                    .map(|v| v * T::lit(0.5) + T::lit(0.5));
+                h = h + offset;
+                let d = p - h;
+                min_dist = min_dist.min(d.dot(d));
+            }
+        }
+    }
+
+    return min_dist.sqrt();
+}
+
+/// See [`wfbm`](super::wfbm)
+pub fn wfbm<T: Real>(p: V3<T>, f: V3<T>, seed: NoiseSeed) -> T {
+    let f = tile_period(f);
+    // wrapped first so a point and its copy a period away round the same in every octave
+    let mut p = p.wrap(f) + V3::lit_array(WFBM_START);
+    let mut t = T::lit(0.0);
+    let mut s = T::lit(1.);
+    let mut c = T::lit(1.);
+
+    for _ in 0..WFBM_OCTAVES {
+        p = p + V3::lit_array(WFBM_OFFSET);
+        let n = worley_noise(p.scale(s), f.scale(s), seed);
+        t = t + n * c;
+        s = s * T::lit(WFBM_LACUNARITY);
+        c = c / T::lit(WFBM_LACUNARITY);
+    }
+    return (T::lit(std::f64::consts::E) - t - T::lit(WFBM_BIAS)).clamp(T::lit(0.0), T::lit(2.0));
+}
+
+/// See [`value_fbm`](super::value_fbm)
+pub fn value_fbm<T: Real>(p: V3<T>, f: V3<T>, seed: NoiseSeed) -> T {
+    let f = tile_period(f);
+    let mut p = p.wrap(f);
+    let mut t = T::lit(0.);
+    let mut s = T::lit(1.);
+    let mut c = T::lit(1.);
+
+    for _ in 0..VALUE_FBM_OCTAVES {
+        p = p + V3::lit_array(VALUE_FBM_OFFSET);
+        t = t + noised(p.scale(s), f.scale(s), seed).0 * c;
+        s = s * T::lit(2.);
+        c = c / T::lit(2.);
+    }
+    return (t / T::lit(VALUE_FBM_DIV) * T::lit(VALUE_FBM_MUL)).clamp(T::lit(0.0), T::lit(2.0));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> impl Iterator<Item = DVec3> {
+        let axis = |i: i32| i as f64 * 1.37 - 6.1;
+        (0..9).flat_map(move |x| {
+            (0..9).flat_map(move |y| (0..9).map(move |z| dvec3(axis(x), axis(y), axis(z))))
+        })
+    }
+
+    fn assert_agree(name: &str, tolerance: f64, noise: impl Fn(DVec3) -> (f32, f64)) {
+        let mut worst = (0.0, DVec3::ZERO);
+        for p in grid() {
+            let (single, double) = noise(p);
+            let error = (single as f64 - double).abs();
+            if error > worst.0 {
+                worst = (error, p);
+            }
+        }
+        assert!(
+            worst.0 <= tolerance,
+            "{} differs by {} between f32 and f64 at {}",
+            name,
+            worst.0,
+            worst.1
+        );
+    }
+
+    #[test]
+    fn f32_agrees_with_f64() {
+        let f = dvec3(4.0, 6.0, f64::INFINITY);
+        let seed = NoiseSeed(5);
+        let both = |p: DVec3| (V3::from(p.as_vec3()), V3::from(p));
+        let (f32_f, f64_f) = (V3::from(f.as_vec3()), V3::from(f));
+        assert_agree("value_noise", 1e-4, |p| {
+            let (a, b) = both(p);
+            (value_noise(a, seed), value_noise(b, seed))
+        });
+        assert_agree("noised", 1e-4, |p| {
+            let (a, b) = both(p);
+            (noised(a, f32_f, seed).0, noised(b, f64_f, seed).0)
+        });
+        assert_agree("noised gradient", 1e-3, |p| {
+            let (a, b) = both(p);
+            let (a, b) = (noised(a, f32_f, seed).1, noised(b, f64_f, seed).1);
+            (a.x + a.y + a.z, b.x + b.y + b.z)
+        });
+        assert_agree("worley_noise", 1e-4, |p| {
+            let (a, b) = both(p);
+            (worley_noise(a, f32_f, seed), worley_noise(b, f64_f, seed))
+        });
+        assert_agree("wfbm", 1e-3, |p| {
+            let (a, b) = both(p);
+            (wfbm(a, f32_f, seed), wfbm(b, f64_f, seed))
+        });
+        // the last octaves sample around 1e4, where f32 only resolves about 1e-3
+        assert_agree("value_fbm", 1e-2, |p| {
+            let (a, b) = both(p);
+            (value_fbm(a, f32_f, seed), value_fbm(b, f64_f, seed))
+        });
+    }
+}
@@ -0,0 +1,243 @@
+// Perlin (gradient) and simplex noise with analytic derivatives.
+//
+// Like `noised`, the value is mapped to roughly [0, 1] so any of these can stand
+// in for value noise, and the derivatives are scaled to match.
+
+use std::cmp::Ordering;
+
+use bevy::{
+    math::{vec3, vec4},
+    prelude::{Vec2, Vec3, Vec4},
+};
+
+use super::{wrap1, NoiseSeed};
+
+/// 2D Perlin noise, returns `(value, d/dx, d/dy)` and repeats every `f` units.
+pub fn perlin2(x: Vec2, f: Vec2, seed: NoiseSeed) -> Vec3 {
+    let (v, d) = gradient_noise(x.to_array(), f.round().max(Vec2::ONE).to_array(), |i| {
+        (seed.hash22(Vec2::from(i)) * 2.0 - 1.0).to_array()
+    });
+    vec3(v, d[0], d[1])
+}
+
+/// 3D Perlin noise, returns `(value, d/dx, d/dy, d/dz)` and repeats every `f` units.
+pub fn perlin3(x: Vec3, f: Vec3, seed: NoiseSeed) -> Vec4 {
+    let (v, d) = gradient_noise(x.to_array(), f.round().max(Vec3::ONE).to_array(), |i| {
+        (seed.hash33(Vec3::from(i)) * 2.0 - 1.0).to_array()
+    });
+    vec4(v, d[0], d[1], d[2])
+}
+
+/// 4D Perlin noise, returns the value and its gradient, repeats every `f` units.
+pub fn perlin4(x: Vec4, f: Vec4, seed: NoiseSeed) -> (f32, Vec4) {
+    let (v, d) = gradient_noise(x.to_array(), f.round().max(Vec4::ONE).to_array(), |i| {
+        (seed.hash44(Vec4::from(i)) * 2.0 - 1.0).to_array()
+    });
+    (v, Vec4::from(d))
+}
+
+/// 2D simplex noise, returns `(value, d/dx, d/dy)`. Simplex noise does not tile.
+pub fn simplex2(x: Vec2, seed: NoiseSeed) -> Vec3 {
+    let (v, d) = simplex_noise(x.to_array(), |i| {
+        (seed.hash22(Vec2::from(i)) * 2.0 - 1.0)
+            .try_normalize()
+            .unwrap_or(Vec2::X)
+            .to_array()
+    });
+    vec3(v, d[0], d[1])
+}
+
+/// 3D simplex noise, returns `(value, d/dx, d/dy, d/dz)`. Simplex noise does not tile.
+pub fn simplex3(x: Vec3, seed: NoiseSeed) -> Vec4 {
+    let (v, d) = simplex_noise(x.to_array(), |i| {
+        (seed.hash33(Vec3::from(i)) * 2.0 - 1.0)
+            .try_normalize()
+            .unwrap_or(Vec3::X)
+            .to_array()
+    });
+    vec4(v, d[0], d[1], d[2])
+}
+
+/// 4D simplex noise, returns the value and its gradient. Simplex noise does not tile.
+pub fn simplex4(x: Vec4, seed: NoiseSeed) -> (f32, Vec4) {
+    let (v, d) = simplex_noise(x.to_array(), |i| {
+        (seed.hash44(Vec4::from(i)) * 2.0 - 1.0)
+            .try_normalize()
+            .unwrap_or(Vec4::X)
+            .to_array()
+    });
+    (v, Vec4::from(d))
+}
+
+// Multilinear blend of the 2^N corner ramps with a quintic fade, the derivative
+// is the product rule over the fade weights and the ramps.
+fn gradient_noise<const N: usize>(
+    x: [f32; N],
+    period: [f32; N],
+    gradient: impl Fn([f32; N]) -> [f32; N],
+) -> (f32, [f32; N]) {
+    let i = x.map(f32::floor);
+    let w: [f32; N] = std::array::from_fn(|k| x[k] - i[k]);
+    let u = w.map(|w| w * w * w * (w * (w * 6.0 - 15.0) + 10.0));
+    let du = w.map(|w| 30.0 * w * w * (w * (w - 2.0) + 1.0));
+
+    let mut value = 0.0;
+    let mut deriv = [0.0; N];
+    for corner in 0..(1 << N) {
+        let c: [f32; N] = std::array::from_fn(|k| ((corner >> k) & 1) as f32);
+        let g = gradient(std::array::from_fn(|k| {
+            wrap1((i[k] + c[k]) as f64, period[k] as f64) as f32
+        }));
+        let d: [f32; N] = std::array::from_fn(|k| w[k] - c[k]);
+        let ramp = dot(g, d);
+
+        let fade = |k: usize| if c[k] == 1.0 { u[k] } else { 1.0 - u[k] };
+        let weight = (0..N).map(fade).product::<f32>();
+        value += weight * ramp;
+        for j in 0..N {
+            let dfade = if c[j] == 1.0 { du[j] } else { -du[j] };
+            let dweight = (0..N).filter(|k| *k != j).map(fade).product::<f32>() * dfade;
+            deriv[j] += weight * g[j] + dweight * ramp;
+        }
+    }
+    (0.5 + 0.5 * value, deriv.map(|d| 0.5 * d))
+}
+
+// Sums the N + 1 corner kernels of the simplex containing x
+fn simplex_noise<const N: usize>(
+    x: [f32; N],
+    gradient: impl Fn([f32; N]) -> [f32; N],
+) -> (f32, [f32; N]) {
+    let n = N as f32;
+    let skew = ((n + 1.0).sqrt() - 1.0) / n;
+    let unskew = (1.0 - 1.0 / (n + 1.0).sqrt()) / n;
+    // kernel radius² and the factor that brings the sum to about [-1, 1]
+    let (r2, scale) = match N {
+        2 => (0.5, 70.0),
+        3 => (0.6, 32.0),
+        _ => (0.6, 27.0),
+    };
+
+    let s = x.iter().sum::<f32>() * skew;
+    let i = x.map(|x| (x + s).floor());
+    let t = i.iter().sum::<f32>() * unskew;
+    let d0: [f32; N] = std::array::from_fn(|k| x[k] - i[k] + t);
+
+    // walk the simplex from the origin corner along the largest offsets first
+    let mut order: [usize; N] = std::array::from_fn(|k| k);
+    order.sort_by(|a, b| d0[*b].partial_cmp(&d0[*a]).unwrap_or(Ordering::Equal));
+
+    let mut value = 0.0;
+    let mut deriv = [0.0; N];
+    let mut c = [0.0; N];
+    for step in 0..=N {
+        if step > 0 {
+            c[order[step - 1]] += 1.0;
+        }
+        let d: [f32; N] = std::array::from_fn(|k| d0[k] - c[k] + step as f32 * unskew);
+        let t = r2 - dot(d, d);
+        if t <= 0.0 {
+            continue;
+        }
+        let g = gradient(std::array::from_fn(|k| i[k] + c[k]));
+        let ramp = dot(g, d);
+        let t2 = t * t;
+        let t4 = t2 * t2;
+        value += t4 * ramp;
+        for k in 0..N {
+            deriv[k] += t4 * g[k] - 8.0 * t2 * t * ramp * d[k];
+        }
+    }
+    (0.5 + 0.5 * scale * value, deriv.map(|d| 0.5 * scale * d))
+}
+
+fn dot<const N: usize>(a: [f32; N], b: [f32; N]) -> f32 {
+    (0..N).map(|k| a[k] * b[k]).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::{NoiseFn, Noised, Perlin, Simplex};
+
+    const H: f32 = 1e-3;
+
+    fn points<const N: usize>() -> impl Iterator<Item = [f32; N]> {
+        (0..64).map(|i| std::array::from_fn(|k| ((i * 7 + k * 13) % 64) as f32 * 0.37 - 9.0))
+    }
+
+    // central differences of the value against the analytic gradient, at points that
+    // cross lattice cells and simplex edges
+    fn check<const N: usize>(name: &str, noise: impl Fn([f32; N]) -> (f32, [f32; N])) {
+        for x in points::<N>() {
+            let (_, d) = noise(x);
+            for k in 0..N {
+                let mut hi = x;
+                let mut lo = x;
+                hi[k] += H;
+                lo[k] -= H;
+                let fd = (noise(hi).0 - noise(lo).0) / (2.0 * H);
+                assert!(
+                    (fd - d[k]).abs() < 2e-2 * d[k].abs().max(1.0),
+                    "{} d/dx{} at {:?}: analytic {}, finite difference {}",
+                    name,
+                    k,
+                    x,
+                    d[k],
+                    fd
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn derivatives_match_finite_differences() {
+        let seed = NoiseSeed(5);
+        let f2 = Vec2::new(4.0, 6.0);
+        let f3 = Vec3::new(4.0, 6.0, 5.0);
+        let f4 = Vec4::new(4.0, 6.0, 5.0, 3.0);
+        check("perlin2", |x| {
+            let v = perlin2(Vec2::from(x), f2, seed);
+            (v.x, [v.y, v.z])
+        });
+        check("perlin3", |x| {
+            let v = perlin3(Vec3::from(x), f3, seed);
+            (v.x, [v.y, v.z, v.w])
+        });
+        check("perlin4", |x| {
+            let (v, d) = perlin4(Vec4::from(x), f4, seed);
+            (v, d.to_array())
+        });
+        check("simplex2", |x| {
+            let v = simplex2(Vec2::from(x), seed);
+            (v.x, [v.y, v.z])
+        });
+        check("simplex3", |x| {
+            let v = simplex3(Vec3::from(x), seed);
+            (v.x, [v.y, v.z, v.w])
+        });
+        check("simplex4", |x| {
+            let (v, d) = simplex4(Vec4::from(x), seed);
+            (v, d.to_array())
+        });
+    }
+
+    #[test]
+    fn sample_d_matches_finite_differences() {
+        let seed = NoiseSeed(5);
+        let period = Some(Vec3::new(4.0, 6.0, 5.0));
+        let noises: [(&str, &dyn NoiseFn, Option<Vec3>); 5] = [
+            ("Perlin", &Perlin { seed }, None),
+            ("Perlin tiling", &Perlin { seed }, period),
+            ("Simplex", &Simplex { seed }, None),
+            ("Noised", &Noised { seed }, None),
+            ("Noised tiling", &Noised { seed }, period),
+        ];
+        for (name, noise, period) in noises {
+            check(name, |x| {
+                let d = noise.sample_d(Vec3::from(x), period);
+                (noise.sample(Vec3::from(x), period), [d.y, d.z, d.w])
+            });
+        }
+    }
+}
@@ -0,0 +1,267 @@
+// A noise described as data: a tree of `NoiseNode`s read from a `.noise.ron` file,
+// built into the same `NoiseFn`s the bakers use. Loaded as an asset, so with
+// `watch_for_changes` an edited file shows up as `AssetEvent::Modified`.
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum NoiseNode {
+    /// [`value_noise`]
+    Value,
+    /// [`noised`]
+    Noised,
+    /// [`perlin3`]
+    Perlin,
+    /// [`simplex3`]
+    Simplex,
+    /// [`worley_noise`]
+    Worley,
+    /// [`wfbm`]
+    Wfbm,
+    /// [`value_fbm`]
+    ValueFbm,
+    /// [`cellular3`]
+    Cellular(CellularSettings),
+    Constant(f32),
+
+    Fractal(Box<NoiseNode>, FractalSettings),
+    Warp {
+        base: Box<NoiseNode>,
+        warps: Vec<NoiseNode>,
+        settings: WarpSettings,
+    },
+    /// See [`Normalized`], the range is measured when the graph is built
+    Normalized(Box<NoiseNode>),
+    /// Samples the input at `p * scale + offset`, periods are scaled along
+    Transform {
+        input: Box<NoiseNode>,
+        #[serde(default = "one")]
+        scale: Vec3,
+        #[serde(default)]
+        offset: Vec3,
+    },
+    /// The input with its seed shifted by this much, to decorrelate copies of a noise
+    Reseed(u32, Box<NoiseNode>),
+
+    /// Linearly maps `from.0..from.1` onto `to.0..to.1`
+    Remap {
+        input: Box<NoiseNode>,
+        from: (f32, f32),
+        to: (f32, f32),
+    },
+    Clamp(Box<NoiseNode>, f32, f32),
+    Abs(Box<NoiseNode>),
+    Add(Vec<NoiseNode>),
+    Mul(Vec<NoiseNode>),
+    Sub(Box<NoiseNode>, Box<NoiseNode>),
+    Min(Box<NoiseNode>, Box<NoiseNode>),
+    Max(Box<NoiseNode>, Box<NoiseNode>),
+    /// `a` to `b` by `t`
+    Mix(Box<NoiseNode>, Box<NoiseNode>, f32),
+}
+
+fn one() -> Vec3 {
+    Vec3::ONE
+}
+
+type Node = Box<dyn NoiseFn>;
+
+fn unary(input: Node, f: impl Fn(f32) -> f32 + Send + Sync + 'static) -> Node {
+    Box::new(FromFn(move |p: Vec3, period: Option<Vec3>| {
+        f(input.sample(p, period))
+    }))
+}
+
+fn binary(a: Node, b: Node, f: impl Fn(f32, f32) -> f32 + Send + Sync + 'static) -> Node {
+    Box::new(FromFn(move |p: Vec3, period: Option<Vec3>| {
+        f(a.sample(p, period), b.sample(p, period))
+    }))
+}
+
+fn fold(inputs: Vec<Node>, init: f32, f: impl Fn(f32, f32) -> f32 + Send + Sync + 'static) -> Node {
+    Box::new(FromFn(move |p: Vec3, period: Option<Vec3>| {
+        inputs
+            .iter()
+            .fold(init, |acc, input| f(acc, input.sample(p, period)))
+    }))
+}
+
+impl NoiseNode {
+    pub fn build(&self, seed: NoiseSeed) -> Box<dyn NoiseFn> {
+        let build = |node: &NoiseNode| node.build(seed);
+        match self {
+            NoiseNode::Value => Box::new(ValueNoise { seed }),
+            NoiseNode::Noised => Box::new(Noised { seed }),
+            NoiseNode::Perlin => Box::new(Perlin { seed }),
+            NoiseNode::Simplex => Box::new(Simplex { seed }),
+            NoiseNode::Worley => Box::new(WorleyNoise { seed }),
+            NoiseNode::Wfbm => Box::new(Wfbm { seed }),
+            NoiseNode::ValueFbm => Box::new(ValueFbm { seed }),
+            NoiseNode::Cellular(settings) => Box::new(Cellular {
+                settings: *settings,
+                seed,
+            }),
+            NoiseNode::Constant(v) => {
+                let v = *v;
+                Box::new(FromFn(move |_: Vec3, _: Option<Vec3>| v))
+            }
+
+            NoiseNode::Fractal(base, settings) => Box::new(Fractal::new(build(base), *settings)),
+            NoiseNode::Warp {
+                base,
+                warps,
+                settings,
+            } => {
+                let mut warp = Warp::new(build(base), *settings);
+                warp.warps = warps.iter().map(build).collect();
+                Box::new(warp)
+            }
+            NoiseNode::Normalized(input) => Box::new(Normalized::new(build(input))),
+            NoiseNode::Transform {
+                input,
+                scale,
+                offset,
+            } => {
+                let (input, scale, offset) = (build(input), *scale, *offset);
+                Box::new(FromFn(move |p: Vec3, period: Option<Vec3>| {
+                    input.sample(p * scale + offset, period.map(|f| f * scale))
+                }))
+            }
+            NoiseNode::Reseed(shift, input) => input.build(NoiseSeed(seed.0.wrapping_add(*shift))),
+
+            NoiseNode::Remap { input, from, to } => {
+                let (from, to) = (*from, *to);
+                unary(build(input), move |v| remap(v, from.0, from.1, to.0, to.1))
+            }
+            NoiseNode::Clamp(input, min, max) => {
+                let (min, max) = (*min, *max);
+                unary(build(input), move |v| v.clamp(min, max))
+            }
+            NoiseNode::Abs(input) => unary(build(input), f32::abs),
+            NoiseNode::Add(inputs) => fold(inputs.iter().map(build).collect(), 0.0, |a, b| a + b),
+            NoiseNode::Mul(inputs) => fold(inputs.iter().map(build).collect(), 1.0, |a, b| a * b),
+            NoiseNode::Sub(a, b) => binary(build(a), build(b), |a, b| a - b),
+            NoiseNode::Min(a, b) => binary(build(a), build(b), f32::min),
+            NoiseNode::Max(a, b) => binary(build(a), build(b), f32::max),
+            NoiseNode::Mix(a, b, t) => {
+                let t = *t;
+                binary(build(a), build(b), move |a, b| a + (b - a) * t)
+            }
+        }
+    }
+
+    /// Whether the noise repeats with the period it's sampled with, [`value_noise`] and
+    /// [`simplex3`] ignore it.
+    pub fn tiles(&self) -> bool {
+        match self {
+            NoiseNode::Value | NoiseNode::Simplex => false,
+            NoiseNode::Noised
+            | NoiseNode::Perlin
+            | NoiseNode::Worley
+            | NoiseNode::Wfbm
+            | NoiseNode::ValueFbm
+            | NoiseNode::Cellular(_)
+            | NoiseNode::Constant(_) => true,
+
+            NoiseNode::Fractal(input, _)
+            | NoiseNode::Normalized(input)
+            | NoiseNode::Transform { input, .. }
+            | NoiseNode::Reseed(_, input)
+            | NoiseNode::Remap { input, .. }
+            | NoiseNode::Clamp(input, ..)
+            | NoiseNode::Abs(input) => input.tiles(),
+            NoiseNode::Warp { base, warps, .. } => base.tiles() && warps.iter().all(Self::tiles),
+            NoiseNode::Add(inputs) | NoiseNode::Mul(inputs) => inputs.iter().all(Self::tiles),
+            NoiseNode::Sub(a, b)
+            | NoiseNode::Min(a, b)
+            | NoiseNode::Max(a, b)
+            | NoiseNode::Mix(a, b, _) => a.tiles() && b.tiles(),
+        }
+    }
+
+    /// The settings of the first [`NoiseNode::Fractal`] in the graph, depth first, to
+    /// tune a graph without rewriting it.
+    pub fn fractal_mut(&mut self) -> Option<&mut FractalSettings> {
+        match self {
+            NoiseNode::Fractal(_, settings) => Some(settings),
+            NoiseNode::Value
+            | NoiseNode::Noised
+            | NoiseNode::Perlin
+            | NoiseNode::Simplex
+            | NoiseNode::Worley
+            | NoiseNode::Wfbm
+            | NoiseNode::ValueFbm
+            | NoiseNode::Cellular(_)
+            | NoiseNode::Constant(_) => None,
+
+            NoiseNode::Normalized(input)
+            | NoiseNode::Transform { input, .. }
+            | NoiseNode::Reseed(_, input)
+            | NoiseNode::Remap { input, .. }
+            | NoiseNode::Clamp(input, ..)
+            | NoiseNode::Abs(input) => input.fractal_mut(),
+            NoiseNode::Warp { base, warps, .. } => std::iter::once(&mut **base)
+                .chain(warps)
+                .find_map(Self::fractal_mut),
+            NoiseNode::Add(inputs) | NoiseNode::Mul(inputs) => {
+                inputs.iter_mut().find_map(Self::fractal_mut)
+            }
+            NoiseNode::Sub(a, b)
+            | NoiseNode::Min(a, b)
+            | NoiseNode::Max(a, b)
+            | NoiseNode::Mix(a, b, _) => a.fractal_mut().or_else(|| b.fractal_mut()),
+        }
+    }
+}
+
+/// A `.noise.ron` file, `root` is the noise that gets baked.
+#[derive(Clone, Debug, Serialize, Deserialize, TypeUuid)]
+#[uuid = "8c3c6b1e-54b2-4b8e-9d7a-2f0f5d1c9a41"]
+pub struct NoiseGraph {
+    pub root: NoiseNode,
+}
+
+impl NoiseGraph {
+    pub fn build(&self, seed: NoiseSeed) -> Box<dyn NoiseFn> {
+        self.root.build(seed)
+    }
+}
+
+#[derive(Default)]
+pub struct NoiseGraphLoader;
+
+impl AssetLoader for NoiseGraphLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let graph: NoiseGraph = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(graph));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["noise.ron"]
+    }
+}
+
+/// Registers [`NoiseGraph`] as an asset loaded from `.noise.ron` files.
+pub struct NoiseGraphPlugin;
+
+impl Plugin for NoiseGraphPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<NoiseGraph>()
+            .init_asset_loader::<NoiseGraphLoader>();
+    }
+}
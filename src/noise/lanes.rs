@@ -0,0 +1,100 @@
+// A fixed number of lanes processed together as one `Real`. `Lanes` is written lane by
+// lane over an array so LLVM turns it into f32x4/f32x8 (or f64x4) vector code, and since
+// every operation is the same IEEE operation as on a plain float the results are
+// bit-identical. The hashes run on `Lanes<u32, N>` the same way.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use super::{generic::Word, Real};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lanes<T, const N: usize>(pub [T; N]);
+
+pub type F32x4 = Lanes<f32, 4>;
+
+impl<T: Real, const N: usize> Lanes<T, N> {
+    pub fn splat(x: T) -> Self {
+        Self([x; N])
+    }
+
+    fn map(self, f: impl Fn(T) -> T) -> Self {
+        Self(self.0.map(f))
+    }
+
+    fn zip(self, other: Self, f: impl Fn(T, T) -> T) -> Self {
+        Self(std::array::from_fn(|i| f(self.0[i], other.0[i])))
+    }
+}
+
+macro_rules! lane_op {
+    ($trait:ident, $fn:ident) => {
+        impl<T: Real, const N: usize> $trait for Lanes<T, N> {
+            type Output = Self;
+            fn $fn(self, other: Self) -> Self {
+                self.zip(other, $trait::$fn)
+            }
+        }
+    };
+}
+
+lane_op!(Add, add);
+lane_op!(Sub, sub);
+lane_op!(Mul, mul);
+lane_op!(Div, div);
+
+impl<T: Real, const N: usize> Neg for Lanes<T, N> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        self.map(Neg::neg)
+    }
+}
+
+impl<T: Real<Cell = u32>, const N: usize> Real for Lanes<T, N> {
+    fn lit(x: f64) -> Self {
+        Self::splat(T::lit(x))
+    }
+    fn floor(self) -> Self {
+        self.map(T::floor)
+    }
+    fn round(self) -> Self {
+        self.map(T::round)
+    }
+    fn sqrt(self) -> Self {
+        self.map(T::sqrt)
+    }
+    fn min(self, other: Self) -> Self {
+        self.zip(other, T::min)
+    }
+    fn max(self, other: Self) -> Self {
+        self.zip(other, T::max)
+    }
+    fn wrap(self, period: Self) -> Self {
+        self.zip(period, T::wrap)
+    }
+    fn wrap_near(self, period: Self) -> Self {
+        self.zip(period, T::wrap_near)
+    }
+
+    type Cell = Lanes<u32, N>;
+    fn cell(self) -> Self::Cell {
+        Lanes(self.0.map(T::cell))
+    }
+    fn unit(h: Self::Cell) -> Self {
+        Lanes(h.0.map(T::unit))
+    }
+}
+
+impl<const N: usize> Word for Lanes<u32, N> {
+    fn word(x: u32) -> Self {
+        Lanes([x; N])
+    }
+    fn add(self, other: Self) -> Self {
+        Lanes(std::array::from_fn(|i| self.0[i].wrapping_add(other.0[i])))
+    }
+    fn mul(self, other: Self) -> Self {
+        Lanes(std::array::from_fn(|i| self.0[i].wrapping_mul(other.0[i])))
+    }
+    fn xorshift(self) -> Self {
+        Lanes(self.0.map(u32::xorshift))
+    }
+}
@@ -0,0 +1,94 @@
+// 4D noise, and sampling it so the 4th axis becomes time that loops seamlessly.
+
+use std::f32::consts::TAU;
+
+use bevy::{
+    math::vec4,
+    prelude::{Vec2, Vec3, Vec4},
+};
+
+use super::{perlin4, wrap1, NoiseSeed};
+
+/// 4D value noise with a cubic fade, repeats every `f` units.
+pub fn value_noise4(x: Vec4, f: Vec4, seed: NoiseSeed) -> f32 {
+    let period = f.round().max(Vec4::ONE);
+    let i = x.floor();
+    let w = x - i;
+    let u = w * w * (3.0 - 2.0 * w);
+
+    let mut value = 0.0;
+    for corner in 0..16 {
+        let c = vec4(
+            (corner & 1) as f32,
+            ((corner >> 1) & 1) as f32,
+            ((corner >> 2) & 1) as f32,
+            ((corner >> 3) & 1) as f32,
+        );
+        let lattice = i + c;
+        let lattice = vec4(
+            wrap1(lattice.x as f64, period.x as f64) as f32,
+            wrap1(lattice.y as f64, period.y as f64) as f32,
+            wrap1(lattice.z as f64, period.z as f64) as f32,
+            wrap1(lattice.w as f64, period.w as f64) as f32,
+        );
+        let weight = (c * u + (Vec4::ONE - c) * (Vec4::ONE - u)).to_array();
+        value += weight.iter().product::<f32>() * seed.hash44(lattice).x;
+    }
+    value
+}
+
+/// Octaves of [`perlin4`] in [0, 1], doubling the frequency each octave so the period holds.
+pub fn perlin_fbm4(p: Vec4, f: Vec4, octaves: u32, seed: NoiseSeed) -> f32 {
+    let f = f.round().max(Vec4::ONE);
+    let mut t = 0.;
+    let mut s = 1.;
+    let mut c = 1.;
+    let mut total = 0.;
+    for _ in 0..octaves {
+        t += perlin4(p * s, f * s, seed).0 * c;
+        total += c;
+        s *= 2.;
+        c *= 0.5;
+    }
+    t / total
+}
+
+/// Point in 4D for `p` at loop time `t` (in cycles): time walks a circle of
+/// `radius` noise units in the zw plane, so `t` and `t + 1` give the same point.
+pub fn loop_point(p: Vec2, t: f32, radius: f32) -> Vec4 {
+    let (s, c) = (t.rem_euclid(1.0) * TAU).sin_cos();
+    vec4(p.x, p.y, c * radius, s * radius)
+}
+
+/// Point in 4D for `p` at loop time `t` (in cycles) when the noise repeats every
+/// `time_period` units along w, the straight alternative to [`loop_point`].
+pub fn periodic_time_point(p: Vec3, t: f32, time_period: f32) -> Vec4 {
+    p.extend(t.rem_euclid(1.0) * time_period.round().max(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loops_repeat_exactly() {
+        let seed = NoiseSeed(6);
+        let f = Vec4::new(4.0, 4.0, f32::INFINITY, f32::INFINITY);
+        let periodic = Vec4::new(4.0, 4.0, 4.0, 5.0);
+        for frame in 0..16 {
+            let t = frame as f32 / 16.0;
+            for i in 0..20 {
+                let p = Vec2::new(i as f32 * 0.37, i as f32 * 0.61);
+                let circle = |t| perlin_fbm4(loop_point(p, t, 1.5), f, 4, seed);
+                let straight = |t| {
+                    let x = periodic_time_point(p.extend(0.3), t, periodic.w);
+                    value_noise4(x, periodic, seed)
+                };
+                for later in [t + 1.0, t + 3.0, t - 2.0] {
+                    assert_eq!(circle(later).to_bits(), circle(t).to_bits());
+                    assert_eq!(straight(later).to_bits(), straight(t).to_bits());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use bevy::{
+    math::vec4,
+    prelude::{Vec3, Vec4},
+};
+
+use super::*;
+
+/// Used in place of a period by noises that shouldn't repeat.
+pub const NO_PERIOD: Vec3 = Vec3::splat(f32::INFINITY);
+
+/// Any seeded 3D noise, so the texture bakers don't need to know which one they sample.
+pub trait NoiseFn: Send + Sync {
+    /// Noise at `p`, repeating every `period` units if one is given (see [`tile_period`]).
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32;
+
+    /// `(value, d/dx, d/dy, d/dz)`, by central differences unless the noise knows better.
+    fn sample_d(&self, p: Vec3, period: Option<Vec3>) -> Vec4 {
+        const H: f32 = 1e-3;
+        let diff = |axis: Vec3| {
+            (self.sample(p + axis * H, period) - self.sample(p - axis * H, period)) / (2.0 * H)
+        };
+        vec4(
+            self.sample(p, period),
+            diff(Vec3::X),
+            diff(Vec3::Y),
+            diff(Vec3::Z),
+        )
+    }
+
+    /// [`sample`](Self::sample) for every point, overridden by the noises with a batch kernel.
+    fn sample_batch(&self, points: &[Vec3], period: Option<Vec3>, out: &mut [f32]) {
+        for (p, out) in points.iter().zip(out) {
+            *out = self.sample(*p, period);
+        }
+    }
+}
+
+impl<N: NoiseFn + ?Sized> NoiseFn for &N {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        (**self).sample(p, period)
+    }
+
+    fn sample_d(&self, p: Vec3, period: Option<Vec3>) -> Vec4 {
+        (**self).sample_d(p, period)
+    }
+
+    fn sample_batch(&self, points: &[Vec3], period: Option<Vec3>, out: &mut [f32]) {
+        (**self).sample_batch(points, period, out)
+    }
+}
+
+impl<N: NoiseFn + ?Sized> NoiseFn for Box<N> {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        (**self).sample(p, period)
+    }
+
+    fn sample_d(&self, p: Vec3, period: Option<Vec3>) -> Vec4 {
+        (**self).sample_d(p, period)
+    }
+
+    fn sample_batch(&self, points: &[Vec3], period: Option<Vec3>, out: &mut [f32]) {
+        (**self).sample_batch(points, period, out)
+    }
+}
+
+/// Wraps a closure, handy for mixing a few noises together before baking.
+pub struct FromFn<F>(pub F);
+
+impl<F: Fn(Vec3, Option<Vec3>) -> f32 + Send + Sync> NoiseFn for FromFn<F> {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        (self.0)(p, period)
+    }
+}
+
+/// [`value_noise`], doesn't tile.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ValueNoise {
+    pub seed: NoiseSeed,
+}
+
+impl NoiseFn for ValueNoise {
+    fn sample(&self, p: Vec3, _period: Option<Vec3>) -> f32 {
+        value_noise(p, self.seed)
+    }
+}
+
+/// [`noised`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Noised {
+    pub seed: NoiseSeed,
+}
+
+impl NoiseFn for Noised {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        noised(p, period.unwrap_or(NO_PERIOD), self.seed).x
+    }
+
+    fn sample_d(&self, p: Vec3, period: Option<Vec3>) -> Vec4 {
+        noised(p, period.unwrap_or(NO_PERIOD), self.seed)
+    }
+
+    fn sample_batch(&self, points: &[Vec3], period: Option<Vec3>, out: &mut [f32]) {
+        noised_batch(points, period.unwrap_or(NO_PERIOD), self.seed, out)
+    }
+}
+
+/// [`perlin3`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Perlin {
+    pub seed: NoiseSeed,
+}
+
+impl NoiseFn for Perlin {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        perlin3(p, period.unwrap_or(NO_PERIOD), self.seed).x
+    }
+
+    fn sample_d(&self, p: Vec3, period: Option<Vec3>) -> Vec4 {
+        perlin3(p, period.unwrap_or(NO_PERIOD), self.seed)
+    }
+}
+
+/// [`simplex3`], doesn't tile.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Simplex {
+    pub seed: NoiseSeed,
+}
+
+impl NoiseFn for Simplex {
+    fn sample(&self, p: Vec3, _period: Option<Vec3>) -> f32 {
+        simplex3(p, self.seed).x
+    }
+
+    fn sample_d(&self, p: Vec3, _period: Option<Vec3>) -> Vec4 {
+        simplex3(p, self.seed)
+    }
+}
+
+/// [`worley_noise`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WorleyNoise {
+    pub seed: NoiseSeed,
+}
+
+impl NoiseFn for WorleyNoise {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        worley_noise(p, period.unwrap_or(NO_PERIOD), self.seed)
+    }
+}
+
+/// [`wfbm`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Wfbm {
+    pub seed: NoiseSeed,
+}
+
+impl NoiseFn for Wfbm {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        wfbm(p, period.unwrap_or(NO_PERIOD), self.seed)
+    }
+
+    fn sample_batch(&self, points: &[Vec3], period: Option<Vec3>, out: &mut [f32]) {
+        wfbm_batch(points, period.unwrap_or(NO_PERIOD), self.seed, out)
+    }
+}
+
+/// [`value_fbm`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ValueFbm {
+    pub seed: NoiseSeed,
+}
+
+impl NoiseFn for ValueFbm {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        value_fbm(p, period.unwrap_or(NO_PERIOD), self.seed)
+    }
+
+    fn sample_batch(&self, points: &[Vec3], period: Option<Vec3>, out: &mut [f32]) {
+        value_fbm_batch(points, period.unwrap_or(NO_PERIOD), self.seed, out)
+    }
+}
+
+/// [`basis_fbm`]
+#[derive(Clone, Copy, Debug)]
+pub struct BasisFbm {
+    pub basis: Basis,
+    pub seed: NoiseSeed,
+}
+
+impl NoiseFn for BasisFbm {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        basis_fbm(p, period.unwrap_or(NO_PERIOD), self.seed, self.basis)
+    }
+}
@@ -0,0 +1,206 @@
+// Measuring what a noise actually outputs, instead of guessing ranges like the
+// `(E - t - 1.25)` and `/ 2.7182817 * 1.75` remaps, and normalising with the result.
+
+use std::f32::consts::TAU;
+
+use bevy::{math::vec3, prelude::Vec3};
+
+use super::NoiseFn;
+
+/// Samples per axis of the grid [`NoiseStats::measure`] uses.
+pub const MEASURE_RES: usize = 64;
+/// Noise units the measuring grid covers on each axis.
+pub const MEASURE_EXTENT: f32 = 37.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoiseStats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub variance: f32,
+    /// Sample counts over `bins` equal steps from `min` to `max`
+    pub histogram: Vec<u32>,
+}
+
+impl NoiseStats {
+    /// Statistics of already baked samples, e.g. a texture from `bake`.
+    pub fn from_samples(data: &[f32], bins: usize) -> Self {
+        let (min, max) = data
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &v| {
+                (lo.min(v), hi.max(v))
+            });
+        // in f64 so a million samples don't lose the mean
+        let n = data.len().max(1) as f64;
+        let mean = data.iter().map(|&v| v as f64).sum::<f64>() / n;
+        let variance = data.iter().map(|&v| (v as f64 - mean).powi(2)).sum::<f64>() / n;
+
+        let mut histogram = vec![0; bins.max(1)];
+        let width = (max - min).max(f32::EPSILON);
+        let last = histogram.len() - 1;
+        for &v in data {
+            let bin = ((v - min) / width * histogram.len() as f32) as usize;
+            histogram[bin.min(last)] += 1;
+        }
+
+        Self {
+            min,
+            max,
+            mean: mean as f32,
+            variance: variance as f32,
+            histogram,
+        }
+    }
+
+    /// Samples `noise` on a `MEASURE_RES`³ grid over `MEASURE_EXTENT` units, without a
+    /// period. The extent isn't a whole number so samples don't all land on lattice points.
+    pub fn measure(noise: &impl NoiseFn, bins: usize) -> Self {
+        let step = MEASURE_EXTENT / MEASURE_RES as f32;
+        let points: Vec<Vec3> = (0..MEASURE_RES.pow(3))
+            .map(|i| {
+                let (x, y, z) = (
+                    i % MEASURE_RES,
+                    i / MEASURE_RES % MEASURE_RES,
+                    i / (MEASURE_RES * MEASURE_RES),
+                );
+                vec3(x as f32, y as f32, z as f32) * step
+            })
+            .collect();
+        let mut data = vec![0.0; points.len()];
+        noise.sample_batch(&points, None, &mut data);
+        Self::from_samples(&data, bins)
+    }
+
+    pub fn std_dev(&self) -> f32 {
+        self.variance.sqrt()
+    }
+
+    /// Value below which roughly `q` (in [0, 1]) of the samples fall, read off the histogram.
+    pub fn percentile(&self, q: f32) -> f32 {
+        let total: u32 = self.histogram.iter().sum();
+        let target = (q.clamp(0.0, 1.0) * total as f32).ceil() as u32;
+        let mut seen = 0;
+        for (i, &count) in self.histogram.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                let t = (i + 1) as f32 / self.histogram.len() as f32;
+                return self.min + (self.max - self.min) * t;
+            }
+        }
+        self.max
+    }
+}
+
+/// Power of a `width × height` texture by spatial frequency: entry `k` is the mean of
+/// |DFT|² over the frequencies `k` cycles per texture from the centre (rounded), up to
+/// half the smaller side. The mean is removed first, so entry 0 is about 0.
+/// White noise is flat, value noise falls off quickly and blue noise rises.
+pub fn radial_power_spectrum(data: &[f32], dims: (usize, usize)) -> Vec<f32> {
+    let (w, h) = dims;
+    assert_eq!(data.len(), w * h);
+    let mean = data.iter().sum::<f32>() / data.len() as f32;
+
+    // separable DFT, rows then columns
+    let rows: Vec<(f32, f32)> = (0..h)
+        .flat_map(|y| {
+            let row: Vec<(f32, f32)> = data[y * w..(y + 1) * w]
+                .iter()
+                .map(|&v| (v - mean, 0.0))
+                .collect();
+            dft(&row)
+        })
+        .collect();
+    let mut spectrum = vec![(0.0, 0.0); w * h];
+    for x in 0..w {
+        let column: Vec<(f32, f32)> = (0..h).map(|y| rows[y * w + x]).collect();
+        for (y, c) in dft(&column).into_iter().enumerate() {
+            spectrum[y * w + x] = c;
+        }
+    }
+
+    let bins = w.min(h) / 2 + 1;
+    let mut power = vec![0.0; bins];
+    let mut counts = vec![0; bins];
+    let signed = |k: usize, n: usize| {
+        if k > n / 2 {
+            k as f32 - n as f32
+        } else {
+            k as f32
+        }
+    };
+    for y in 0..h {
+        for x in 0..w {
+            let r = signed(x, w).hypot(signed(y, h)).round() as usize;
+            if r < bins {
+                let (re, im) = spectrum[y * w + x];
+                power[r] += re * re + im * im;
+                counts[r] += 1;
+            }
+        }
+    }
+    let n = (w * h) as f32;
+    power
+        .iter()
+        .zip(counts)
+        .map(|(p, c)| p / c.max(1) as f32 / n)
+        .collect()
+}
+
+fn dft(input: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let n = input.len();
+    let twiddle: Vec<(f32, f32)> = (0..n)
+        .map(|k| {
+            let a = -TAU * k as f32 / n as f32;
+            (a.cos(), a.sin())
+        })
+        .collect();
+    (0..n)
+        .map(|k| {
+            input
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(sr, si), (j, &(re, im))| {
+                    let (c, s) = twiddle[k * j % n];
+                    (sr + re * c - im * s, si + re * s + im * c)
+                })
+        })
+        .collect()
+}
+
+/// `base` remapped so its output covers [0, 1]: `(v - min) / (max - min)`, clamped.
+/// [`Normalized::new`] measures the range, so values beyond what the measuring grid
+/// saw (rare for fractals) clamp to 0 or 1.
+#[derive(Clone, Debug)]
+pub struct Normalized<N> {
+    pub base: N,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl<N: NoiseFn> Normalized<N> {
+    pub fn new(base: N) -> Self {
+        let stats = NoiseStats::measure(&base, 1);
+        Self::with_range(base, stats.min, stats.max)
+    }
+
+    pub fn with_range(base: N, min: f32, max: f32) -> Self {
+        Self { base, min, max }
+    }
+
+    fn remap(&self, v: f32) -> f32 {
+        ((v - self.min) / (self.max - self.min).max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+}
+
+impl<N: NoiseFn> NoiseFn for Normalized<N> {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        self.remap(self.base.sample(p, period))
+    }
+
+    fn sample_batch(&self, points: &[Vec3], period: Option<Vec3>, out: &mut [f32]) {
+        self.base.sample_batch(points, period, out);
+        for v in out {
+            *v = self.remap(*v);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use bevy::{
+    math::vec3,
+    prelude::{Reflect, Vec3},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::NoiseFn;
+
+// Where the y and z displacements are read from the warp field. z is left at 0 so
+// a 2D texture of the field at z = 0 warps the same way, see `noise_warp.wgsl`.
+pub const WARP_OFFSETS: [Vec3; 2] = [Vec3::new(5.2, 1.3, 0.0), Vec3::new(1.7, 9.2, 0.0)];
+
+#[derive(Clone, Copy, Debug, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WarpSettings {
+    /// Displacement in noise units for a warp field value of 0 or 1
+    pub strength: f32,
+    pub iterations: u32,
+}
+
+impl Default for WarpSettings {
+    fn default() -> Self {
+        Self {
+            strength: 0.0,
+            iterations: 1,
+        }
+    }
+}
+
+/// `base` sampled at `p` displaced by the sum of every warp field's displacement,
+/// repeated `iterations` times. Each field is read at the point the fields before it
+/// warped to.
+/// Tiles whenever the base and the warp fields do.
+pub struct Warp<N> {
+    pub base: N,
+    pub warps: Vec<Box<dyn NoiseFn>>,
+    pub settings: WarpSettings,
+}
+
+impl<N: NoiseFn> Warp<N> {
+    pub fn new(base: N, settings: WarpSettings) -> Self {
+        Self {
+            base,
+            warps: Vec::new(),
+            settings,
+        }
+    }
+
+    pub fn with(mut self, warp: impl NoiseFn + 'static) -> Self {
+        self.warps.push(Box::new(warp));
+        self
+    }
+}
+
+/// The warp field read as a displacement in [-1, 1] on each axis
+pub fn displacement(field: &dyn NoiseFn, p: Vec3, period: Option<Vec3>) -> Vec3 {
+    vec3(
+        field.sample(p, period),
+        field.sample(p + WARP_OFFSETS[0], period),
+        field.sample(p + WARP_OFFSETS[1], period),
+    ) * 2.0
+        - 1.0
+}
+
+impl<N: NoiseFn> NoiseFn for Warp<N> {
+    fn sample(&self, p: Vec3, period: Option<Vec3>) -> f32 {
+        let mut q = p;
+        for _ in 0..self.settings.iterations {
+            let mut offset = Vec3::ZERO;
+            for warp in &self.warps {
+                offset += self.settings.strength * displacement(warp.as_ref(), q, period);
+                q = p + offset;
+            }
+        }
+        self.base.sample(q, period)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::{FromFn, NoiseSeed, Noised};
+
+    #[test]
+    fn warp_fields_add_up() {
+        // constant fields displace by `strength * (2v - 1)` on every axis
+        let field = |v: f32| FromFn(move |_: Vec3, _: Option<Vec3>| v);
+        let settings = WarpSettings {
+            strength: 0.5,
+            iterations: 1,
+        };
+        let position = FromFn(|p: Vec3, _: Option<Vec3>| p.x + 10.0 * p.y + 100.0 * p.z);
+        let warp = Warp::new(&position, settings)
+            .with(field(1.0))
+            .with(field(0.75));
+        let p = vec3(1.0, 2.0, 3.0);
+        assert_eq!(warp.sample(p, None), position.sample(p + 0.75, None));
+
+        // each field reads the point the ones before it warped to
+        let seed = NoiseSeed(2);
+        let base = Noised { seed };
+        let (a, b) = (Noised { seed: NoiseSeed(3) }, Noised { seed: NoiseSeed(4) });
+        let warp = Warp::new(base, settings).with(a).with(b);
+        let first = 0.5 * displacement(&a, p, None);
+        let second = 0.5 * displacement(&b, p + first, None);
+        assert_eq!(warp.sample(p, None), base.sample(p + first + second, None));
+    }
+}
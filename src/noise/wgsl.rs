@@ -0,0 +1,264 @@
+// WGSL versions of the lattice noises, generated from the constants the CPU code in
+// `generic.rs` uses so the two can't drift apart. Same structure as the Rust: cubic
+// fade, the same hashes, octave offsets and remaps. Periods work like `tile_period`
+// except 0 stands in for "doesn't tile", since WGSL has no infinity literal. The tests
+// validate the output with naga and pin the f32 CPU values it should reproduce.
+
+use super::generic::*;
+use super::NoiseSeed;
+
+const TEMPLATE: &str = r#"#define_import_path portfolio::noise
+
+// Generated by noise::noise_wgsl, don't edit by hand.
+
+// NoiseSeed::keys
+const NOISE_SEED_KEYS: vec3<u32> = $SEED_KEYS;
+
+// noise::wrap1, a period of 0 leaves that axis untiled
+fn noise_wrap(i: vec3<f32>, f: vec3<f32>) -> vec3<f32> {
+    return select(i, i - f * floor(i / f), f > vec3(0.0));
+}
+
+// noise::tile_period, both round ties to even
+fn noise_tile_period(f: vec3<f32>) -> vec3<f32> {
+    return select(vec3(0.0), max(round(f), vec3(1.0)), f > vec3(0.0));
+}
+
+// generic::pcg3d
+fn noise_pcg3d(v: vec3<u32>) -> vec3<u32> {
+    var v = v * 1664525u + 1013904223u;
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    v ^= v >> vec3(16u);
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    return v;
+}
+
+// generic::hash33, `p` is a lattice cell
+fn noise_hash33(p: vec3<f32>) -> vec3<f32> {
+    let h = noise_pcg3d(bitcast<vec3<u32>>(vec3<i32>(p)) + NOISE_SEED_KEYS);
+    return vec3<f32>(h >> vec3(8u)) / 16777216.0;
+}
+
+fn noise_hash(p: vec3<f32>) -> f32 {
+    return noise_hash33(p).x;
+}
+
+// noise::noised, (value, d/dx, d/dy, d/dz)
+fn noise_value(x: vec3<f32>, f: vec3<f32>) -> vec4<f32> {
+    let period = noise_tile_period(f);
+    let i = floor(x);
+    let w = fract(x);
+    let u = w * w * (3.0 - 2.0 * w);
+    let du = 6.0 * w * (1.0 - w);
+    let a = noise_hash(noise_wrap(i + vec3(0.0, 0.0, 0.0), period));
+    let b = noise_hash(noise_wrap(i + vec3(1.0, 0.0, 0.0), period));
+    let c = noise_hash(noise_wrap(i + vec3(0.0, 1.0, 0.0), period));
+    let d = noise_hash(noise_wrap(i + vec3(1.0, 1.0, 0.0), period));
+    let e = noise_hash(noise_wrap(i + vec3(0.0, 0.0, 1.0), period));
+    let f = noise_hash(noise_wrap(i + vec3(1.0, 0.0, 1.0), period));
+    let g = noise_hash(noise_wrap(i + vec3(0.0, 1.0, 1.0), period));
+    let h = noise_hash(noise_wrap(i + vec3(1.0, 1.0, 1.0), period));
+
+    let k0 = a;
+    let k1 = b - a;
+    let k2 = c - a;
+    let k3 = e - a;
+    let k4 = a - b - c + d;
+    let k5 = a - c - e + g;
+    let k6 = a - b - e + f;
+    let k7 = -a + b + c - d + e - f - g + h;
+
+    let deriv = du * vec3(
+        k1 + k4 * u.y + k6 * u.z + k7 * u.y * u.z,
+        k2 + k5 * u.z + k4 * u.x + k7 * u.z * u.x,
+        k3 + k6 * u.x + k5 * u.y + k7 * u.x * u.y,
+    );
+    return vec4(
+        k0 + k1 * u.x + k2 * u.y + k3 * u.z + k4 * u.x * u.y + k5 * u.y * u.z + k6 * u.z * u.x + k7 * u.x * u.y * u.z,
+        deriv,
+    );
+}
+
+// noise::worley_noise
+fn noise_worley(p: vec3<f32>, f: vec3<f32>) -> f32 {
+    let period = noise_tile_period(f);
+    let id = floor(p);
+    let p = fract(p);
+
+    var min_dist = 10000.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var z = -1; z <= 1; z++) {
+                let offset = vec3(f32(x), f32(y), f32(z));
+                let h = noise_hash33(noise_wrap(id + offset, period)) * 0.5 + 0.5 + offset;
+                let d = p - h;
+                min_dist = min(min_dist, dot(d, d));
+            }
+        }
+    }
+    return sqrt(min_dist);
+}
+
+// noise::wfbm
+fn noise_wfbm(p: vec3<f32>, f: vec3<f32>) -> f32 {
+    let f = noise_tile_period(f);
+    var p = noise_wrap(p, f) + $WFBM_START;
+    var t = 0.0;
+    var s = 1.0;
+    var c = 1.0;
+    for (var i = 0u; i < $WFBM_OCTAVESu; i++) {
+        p += $WFBM_OFFSET;
+        t += noise_worley(p * s, f * s) * c;
+        s *= $WFBM_LACUNARITY;
+        c /= $WFBM_LACUNARITY;
+    }
+    return clamp($E - t - $WFBM_BIAS, 0.0, 2.0);
+}
+
+// noise::value_fbm
+fn noise_value_fbm(p: vec3<f32>, f: vec3<f32>) -> f32 {
+    let f = noise_tile_period(f);
+    var p = noise_wrap(p, f);
+    var t = 0.0;
+    var s = 1.0;
+    var c = 1.0;
+    for (var i = 0u; i < $VALUE_FBM_OCTAVESu; i++) {
+        p += $VALUE_FBM_OFFSET;
+        t += noise_value(p * s, f * s).x * c;
+        s *= 2.0;
+        c /= 2.0;
+    }
+    return clamp(t / $VALUE_FBM_DIV * $VALUE_FBM_MUL, 0.0, 2.0);
+}
+"#;
+
+fn float(x: f64) -> String {
+    format!("{:?}", x as f32)
+}
+
+fn vector(v: [f64; 3]) -> String {
+    format!(
+        "vec3<f32>({}, {}, {})",
+        float(v[0]),
+        float(v[1]),
+        float(v[2])
+    )
+}
+
+/// The `portfolio::noise` shader module, with `seed`'s keys baked in. It runs the CPU
+/// formulas in f32 on the same integer hashes, so the lattice values are exactly the
+/// CPU's and the noise is the f32 CPU noise up to the GPU's float rounding.
+pub fn noise_wgsl(seed: NoiseSeed) -> String {
+    let keys = seed.keys();
+    [
+        (
+            "$SEED_KEYS",
+            format!("vec3<u32>({}u, {}u, {}u)", keys[0], keys[1], keys[2]),
+        ),
+        ("$WFBM_OCTAVES", WFBM_OCTAVES.to_string()),
+        ("$WFBM_START", vector(WFBM_START)),
+        ("$WFBM_OFFSET", vector(WFBM_OFFSET)),
+        ("$WFBM_LACUNARITY", float(WFBM_LACUNARITY)),
+        ("$WFBM_BIAS", float(WFBM_BIAS)),
+        ("$VALUE_FBM_OCTAVES", VALUE_FBM_OCTAVES.to_string()),
+        ("$VALUE_FBM_OFFSET", vector(VALUE_FBM_OFFSET)),
+        ("$VALUE_FBM_DIV", float(VALUE_FBM_DIV)),
+        ("$VALUE_FBM_MUL", float(VALUE_FBM_MUL)),
+        ("$E", float(std::f64::consts::E)),
+    ]
+    .iter()
+    .fold(TEMPLATE.to_string(), |source, (name, value)| {
+        source.replace(name, value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec3;
+    use naga::{
+        valid::{Capabilities, ValidationFlags, Validator},
+        Module,
+    };
+
+    use super::*;
+    use crate::noise::{noised, value_fbm, wfbm, worley_noise};
+
+    const FUNCTIONS: [&str; 4] = [
+        "noise_value",
+        "noise_worley",
+        "noise_wfbm",
+        "noise_value_fbm",
+    ];
+
+    fn parse(seed: NoiseSeed) -> Module {
+        // the import path is for bevy's preprocessor, naga doesn't know it
+        let source = noise_wgsl(seed).replace("#define_import_path portfolio::noise", "");
+        let module = naga::front::wgsl::parse_str(&source)
+            .unwrap_or_else(|e| panic!("{}", e.emit_to_string(&source)));
+        if let Err(e) =
+            Validator::new(ValidationFlags::all(), Capabilities::empty()).validate(&module)
+        {
+            panic!("generated WGSL doesn't validate: {:?}", e);
+        }
+        return module;
+    }
+
+    #[test]
+    fn generated_wgsl_validates() {
+        for seed in [NoiseSeed(0), NoiseSeed(9), NoiseSeed(u32::MAX)] {
+            let module = parse(seed);
+            for name in FUNCTIONS {
+                assert!(
+                    module
+                        .functions
+                        .iter()
+                        .any(|(_, f)| f.name.as_deref() == Some(name)),
+                    "no {} in the generated WGSL",
+                    name
+                );
+            }
+        }
+    }
+
+    // What the shader functions should return at a few points, from the f32 CPU noises
+    // the WGSL mirrors operation for operation. A change to the formulas or constants on either side has to
+    // update these.
+    #[test]
+    fn cpu_reference_values() {
+        let seed = NoiseSeed(9);
+        let f = vec3(4.0, 6.0, f32::INFINITY);
+        let points = [
+            vec3(0.3, 1.7, -2.2),
+            vec3(5.5, -3.25, 7.1),
+            vec3(-12.8, 4.4, 0.6),
+        ];
+        let expected: [[f32; 4]; 3] = [
+            [0.64115465, 0.28090817, 0.7801955, 0.690446],
+            [0.44284004, 0.37943172, 0.84028816, 0.7442224],
+            [0.5437803, 0.60664964, 0.72821283, 0.63405806],
+        ];
+        for (p, expected) in points.into_iter().zip(expected) {
+            let cpu = [
+                noised(p, f, seed).x,
+                worley_noise(p, f, seed),
+                wfbm(p, f, seed),
+                value_fbm(p, f, seed),
+            ];
+            for (name, (cpu, expected)) in FUNCTIONS.into_iter().zip(cpu.into_iter().zip(expected))
+            {
+                assert!(
+                    (cpu - expected).abs() <= 1e-6,
+                    "{} at {}: {} but {} is pinned",
+                    name,
+                    p,
+                    cpu,
+                    expected
+                );
+            }
+        }
+    }
+}
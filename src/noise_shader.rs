@@ -1,40 +1,39 @@
-//! A shader and a material that uses it.
+//! The shared noise shader imports: the generated `portfolio::noise` and the WGSL files
+//! next to it.
 
-use bevy::{
-    prelude::*,
-    reflect::TypeUuid,
-    render::render_resource::{AsBindGroup, ShaderRef},
-};
+use bevy::{prelude::*, reflect::TypeUuid};
+use resume::noise::{self, NoiseSeed};
 
-/// The Material trait is very configurable, but comes with sensible defaults for all methods.
-/// You only need to implement functions for features that need non-default behavior. See the Material api docs for details!
-impl Material for NoiseMaterial {
-    fn fragment_shader() -> ShaderRef {
-        "shaders/Noise.wgsl".into()
-    }
+/// `portfolio::noise`, generated from the CPU noise by [`noise::noise_wgsl`].
+const NOISE_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x5e1f_93c2_4d7a_0b61);
 
-    fn alpha_mode(&self) -> AlphaMode {
-        self.alpha_mode
-    }
-}
+/// Loads the shared noise shaders so materials can `#import` them.
+pub struct NoiseShaderPlugin;
 
-// This is the struct that will be passed to your shader
-#[derive(AsBindGroup, TypeUuid, Debug, Clone)]
-#[uuid = "f690fdae-d598-45ab-8225-97e2056056e0"]
-pub struct NoiseMaterial {
-    #[uniform(0)]
-    pub octaves: i32,
-    #[uniform(0)]
-    pub scale: f32,
-    #[uniform(0)]
-    pub contribution: f32,
+// only held so the imported shaders stay loaded
+#[derive(Resource)]
+struct NoiseShaderImports(#[allow(dead_code)] Vec<Handle<Shader>>);
 
-    #[uniform(0)]
-    pub falloff: f32,
-    #[uniform(0)]
-    pub threshold: f32,
-    #[texture(1)]
-    #[sampler(2)]
-    pub color_texture: Option<Handle<Image>>,
-    pub alpha_mode: AlphaMode,
+impl Plugin for NoiseShaderPlugin {
+    fn build(&self, app: &mut App) {
+        let asset_server = app.world.resource::<AssetServer>();
+        let imports = vec![
+            asset_server.load("shaders/noise_warp.wgsl"),
+            asset_server.load("shaders/blue_noise.wgsl"),
+        ];
+        app.insert_resource(NoiseShaderImports(imports));
+
+        app.init_resource::<NoiseSeed>();
+        app.add_system(
+            |seed: Res<NoiseSeed>, mut shaders: ResMut<Assets<Shader>>| {
+                if seed.is_changed() {
+                    shaders.set_untracked(
+                        NOISE_SHADER_HANDLE,
+                        Shader::from_wgsl(noise::noise_wgsl(*seed)),
+                    );
+                }
+            },
+        );
+    }
 }
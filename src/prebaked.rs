@@ -0,0 +1,160 @@
+//! Textures baked ahead of time by the `bake` binary, so the app can load them instead of
+//! baking at startup. Each one is a float KTX2 file in [`PREBAKED_DIR`] whose key/value
+//! data records the seed and parameters it was baked from and its decode range. A file
+//! that no longer matches what the app would bake is ignored.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{asset::AssetIo, log::warn, math::vec2, prelude::*};
+
+use crate::{export, noise::NoiseSeed};
+
+/// Directory in the asset folder the `bake` binary writes to.
+pub const PREBAKED_DIR: &str = "baked";
+
+const SEED_KEY: &str = "resume.seed";
+const PARAMS_KEY: &str = "resume.params";
+const RANGE_KEY: &str = "resume.range";
+
+/// What a texture was baked from, `params` is a [`crate::cache::param_hash`] of the
+/// generator's parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BakeKey {
+    pub seed: NoiseSeed,
+    pub params: u64,
+}
+
+/// Asset path of the prebaked copy of `name`, `baked/clouds/worley.ktx2` for
+/// `clouds/worley`.
+pub fn path(name: &str) -> PathBuf {
+    return Path::new(PREBAKED_DIR).join(name).with_extension("ktx2");
+}
+
+fn invalid(message: String) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+/// Writes a prebaked texture, `range` as in [`crate::bake::Encoded::range`].
+pub fn write(image: &Image, key: BakeKey, range: Vec2, path: impl AsRef<Path>) -> io::Result<()> {
+    let seed = key.seed.0.to_string();
+    let params = format!("{:016x}", key.params);
+    let range = format!("{} {}", range.x, range.y);
+    return export::write_ktx2(
+        image,
+        &[
+            (SEED_KEY, &seed),
+            (PARAMS_KEY, &params),
+            (RANGE_KEY, &range),
+        ],
+        path,
+    );
+}
+
+/// The key and decode range [`write`] stored in a file.
+pub fn read_key(bytes: &[u8]) -> io::Result<(BakeKey, Vec2)> {
+    let key_values = export::ktx2_key_values(bytes)?;
+    let get = |key: &str| {
+        return key_values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .ok_or_else(|| invalid(format!("no {}", key)));
+    };
+    let seed = get(SEED_KEY)?
+        .parse()
+        .map_err(|_| invalid(format!("bad {}", SEED_KEY)))?;
+    let params = u64::from_str_radix(get(PARAMS_KEY)?, 16)
+        .map_err(|_| invalid(format!("bad {}", PARAMS_KEY)))?;
+    let range: Vec<f32> = get(RANGE_KEY)?
+        .split(' ')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid(format!("bad {}", RANGE_KEY)))?;
+    let [min, max] = range[..] else {
+        return Err(invalid(format!("bad {}", RANGE_KEY)));
+    };
+    let key = BakeKey {
+        seed: NoiseSeed(seed),
+        params,
+    };
+    return Ok((key, vec2(min, max)));
+}
+
+/// The prebaked copy of `name` and its decode range, if there is one baked from `key`.
+/// Read through `asset_io`, so it's found wherever the `AssetServer` looks. Stale or
+/// unreadable files are logged and skipped.
+pub async fn load(asset_io: &dyn AssetIo, name: &str, key: BakeKey) -> Option<(Image, Vec2)> {
+    let path = path(name);
+    let bytes = asset_io.load_path(&path).await.ok()?;
+    let prebaked = read_key(&bytes).and_then(|(baked_from, range)| {
+        if baked_from != key {
+            return Err(invalid(format!(
+                "baked from {:?} instead of {:?}",
+                baked_from, key
+            )));
+        }
+        return Ok((export::ktx2_image(&bytes)?, range));
+    });
+    return match prebaked {
+        Ok(prebaked) => Some(prebaked),
+        Err(e) => {
+            warn!("Ignoring prebaked {:?}: {}", path, e);
+            None
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        asset::FileAssetIo,
+        render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    };
+    use futures_lite::future::block_on;
+
+    use super::*;
+    use crate::bake;
+
+    #[test]
+    fn loads_only_matching_bakes() {
+        let root = std::env::temp_dir().join(format!("resume-prebaked-{}", std::process::id()));
+        std::fs::create_dir_all(root.join(PREBAKED_DIR).join("clouds")).unwrap();
+        let asset_io = FileAssetIo::new(&root, false);
+
+        let data: Vec<f32> = (0..16).map(|i| i as f32 * 0.25 - 1.0).collect();
+        let image = Image::new(
+            Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            bake::r32_bytes(&data),
+            TextureFormat::R32Float,
+        );
+        let key = BakeKey {
+            seed: NoiseSeed(7),
+            params: 0x0123_4567_89ab_cdef,
+        };
+        let range = vec2(-0.3, 1.7);
+        write(&image, key, range, root.join(path("clouds/worley"))).unwrap();
+
+        let load = |name: &str, key: BakeKey| block_on(load(&asset_io, name, key));
+        let (loaded, loaded_range) = load("clouds/worley", key).unwrap();
+        assert_eq!(loaded.data, image.data);
+        assert_eq!(loaded_range, range);
+        let reseeded = BakeKey {
+            seed: NoiseSeed(8),
+            ..key
+        };
+        assert!(load("clouds/worley", reseeded).is_none());
+        let edited = BakeKey { params: 1, ..key };
+        assert!(load("clouds/worley", edited).is_none());
+        assert!(load("clouds/value", key).is_none());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,674 @@
+//! Generated textures described as data.
+//!
+//! A `.proctex.ron` file names a generator and the image to bake it into:
+//!
+//! ```ron
+//! (
+//!     generator: CloudShape(frequency: 4.0),
+//!     resolution: (128, 128, 128),
+//!     dimension: D3,
+//!     format: Float32,
+//!     seed: 0,
+//! )
+//! ```
+//!
+//! [`ProcTexLoader`] bakes a low resolution placeholder while loading, so it's loaded
+//! with `AssetServer::load` like any other texture and re-baked when the file, or the
+//! `.noise.ron` graph or `.sdf.ron` tree its generator reads, is edited. The full texture
+//! is baked on the async compute pool by [`PendingBakes`] and swapped in when it's done.
+//! What a texture was loaded from is kept, see [`loaded_from`], so it can be tuned and
+//! re-baked at runtime. `seed` is added to the app's [`NoiseSeed`] and every texture is
+//! re-baked when that changes. A copy the `bake` binary wrote is loaded instead of baking
+//! while it matches, see [`crate::prebaked`]. Every texture but the distance fields
+//! tiles, and gets a Kaiser mip chain that wraps where it does. The decode range of the
+//! quantised formats is a labeled `range` asset, [`ProcTexPlugin`] copies it into
+//! [`PendingBakes`] so materials read it back with [`PendingBakes::range`].
+
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, HandleId, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_resource::{AddressMode, Extent3d, SamplerDescriptor, TextureDimension},
+        renderer::RenderDevice,
+        settings::WgpuFeatures,
+        texture::ImageSampler,
+    },
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    async_bake::{Baked, PendingBakes},
+    bake, cache,
+    export::TextureKind,
+    mips::{self, MipFilter},
+    noise::{self, BasisKind, FractalSettings, NoiseGraph, NoiseNode, NoiseSeed},
+    prebaked::{self, BakeKey},
+    sdf::SdfNode,
+};
+
+// bump when a generator bakes something else from the same parameters
+const GENERATOR_VERSION: u32 = 3;
+
+/// Largest side of a placeholder, see [`ProcTex::placeholder`].
+pub const PLACEHOLDER_RES: u32 = 32;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Generator {
+    /// A noise graph (see [`NoiseNode`]) tiling `scale` times across the texture, `D2` or
+    /// `D3`. Graphs that don't tile (see [`NoiseNode::tiles`]) are rejected
+    Noise { noise: NoiseNode, scale: Vec3 },
+    /// The [`NoiseGraph`] in the `.noise.ron` file at `path` in the asset folder, baked
+    /// like `Noise`
+    Graph { path: String, scale: Vec3 },
+    /// The distances of a signed distance field (see [`SdfNode`]) over -1 to 1 on every
+    /// axis, `D3` only. It doesn't tile, so its mips clamp at the edges and so does its
+    /// sampler
+    Sdf { sdf: SdfNode },
+    /// The [`SdfNode`] in the `.sdf.ron` file at `path` in the asset folder, baked like
+    /// `Sdf`
+    SdfFile { path: String },
+    /// The Perlin-Worley shape volume, see [`noise::cloud_shape`]. `basis` has to tile,
+    /// see [`BasisKind::tiles`]
+    CloudShape {
+        frequency: f32,
+        #[serde(default)]
+        basis: BasisKind,
+    },
+    /// The Worley detail volume, see [`noise::cloud_detail`]
+    CloudDetail { frequency: f32 },
+    /// A curl noise flow field, see [`noise::curl_noise`]
+    Curl { frequency: f32 },
+    /// A looping animation of Perlin fbm, one frame per layer of a `D2Array`, see
+    /// [`bake::looping_texture_array`]
+    LoopingFbm {
+        scale: Vec2,
+        radius: f32,
+        octaves: u32,
+    },
+}
+
+/// A `.proctex.ron` file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProcTex {
+    pub generator: Generator,
+    /// Width, height and depth, or layer count for a `D2Array`
+    pub resolution: (u32, u32, u32),
+    pub dimension: TextureKind,
+    /// Falls back to `Float32` when the device can't sample `Unorm16`, see
+    /// [`bake::TexelEncoding::supported`]
+    #[serde(default)]
+    pub format: bake::TexelEncoding,
+    /// Offset from the app's [`NoiseSeed`], so two textures of the same noise can differ
+    #[serde(default)]
+    pub seed: u32,
+}
+
+/// What the texels of a baked [`ProcTex`] decode to, see [`bake::Encoded::range`].
+#[derive(Clone, Copy, Debug, TypeUuid)]
+#[uuid = "3f6e2a8d-91c4-4c0b-b7de-5a2f8e61d0c7"]
+pub struct DecodeRange(pub Vec2);
+
+/// The [`ProcTex`] an image was loaded from, labeled `texture`. Its graph is inlined and
+/// its format one the device supports, so it bakes as is.
+#[derive(Clone, Debug, TypeUuid)]
+#[uuid = "b0d4c5e7-2a61-4f3e-9c8b-71e5d2a9f046"]
+pub struct LoadedProcTex {
+    pub texture: ProcTex,
+    /// The app's seed when it was loaded
+    pub seed: NoiseSeed,
+    /// Whether the image is a placeholder the full bake still has to replace
+    pub placeholder: bool,
+}
+
+impl ProcTex {
+    /// Replaces a `Graph` generator with the `Noise` in `graph`, the contents of its file.
+    pub fn inline_graph(&mut self, graph: &[u8]) -> Result<(), ron::error::SpannedError> {
+        if let Generator::Graph { scale, .. } = self.generator {
+            let graph: NoiseGraph = ron::de::from_bytes(graph)?;
+            self.generator = Generator::Noise {
+                noise: graph.root,
+                scale,
+            };
+        }
+        return Ok(());
+    }
+
+    /// Replaces an `SdfFile` generator with the `Sdf` in `sdf`, the contents of its file,
+    /// see [`SdfNode::from_ron`].
+    pub fn inline_sdf(&mut self, sdf: &[u8]) -> Result<(), String> {
+        if let Generator::SdfFile { .. } = self.generator {
+            self.generator = Generator::Sdf {
+                sdf: SdfNode::from_ron(sdf)?,
+            };
+        }
+        return Ok(());
+    }
+
+    /// The file a `Graph` or `SdfFile` generator reads, relative to the asset folder.
+    pub fn reads(&self) -> Option<&str> {
+        return match &self.generator {
+            Generator::Graph { path, .. } | Generator::SdfFile { path } => Some(path),
+            _ => None,
+        };
+    }
+
+    /// The settings of the first fractal in a `Noise` generator, see
+    /// [`NoiseNode::fractal_mut`]. `Graph`s have to be inlined first.
+    pub fn fractal_mut(&mut self) -> Option<&mut FractalSettings> {
+        return match &mut self.generator {
+            Generator::Noise { noise, .. } => noise.fractal_mut(),
+            _ => None,
+        };
+    }
+
+    /// What [`ProcTex::bake`] bakes from, the resolution and format aside. `Graph`s
+    /// should be inlined first so edits to them count.
+    pub fn bake_key(&self, seed: NoiseSeed) -> BakeKey {
+        let params = ron::to_string(&(&self.generator, self.dimension)).unwrap_or_default();
+        return BakeKey {
+            seed: NoiseSeed(seed.0.wrapping_add(self.seed)),
+            params: cache::param_hash(&(GENERATOR_VERSION, params)),
+        };
+    }
+
+    /// The same texture at most [`PLACEHOLDER_RES`] texels on a side, quick enough to
+    /// bake while loading. Animations keep their frames.
+    pub fn placeholder(&self) -> ProcTex {
+        let (width, height, depth) = self.resolution;
+        let depth = match self.dimension {
+            TextureKind::D3 => depth.min(PLACEHOLDER_RES),
+            _ => depth,
+        };
+        return ProcTex {
+            resolution: (
+                width.min(PLACEHOLDER_RES),
+                height.min(PLACEHOLDER_RES),
+                depth,
+            ),
+            ..self.clone()
+        };
+    }
+
+    pub fn bake(&self, seed: NoiseSeed) -> Result<Baked, String> {
+        let seed = NoiseSeed(seed.0.wrapping_add(self.seed));
+        let (width, height, depth) = self.resolution;
+        let res = (width as usize, height as usize, depth as usize);
+        let encoding = self.format;
+        let encoded = match (&self.generator, self.dimension) {
+            (Generator::Noise { noise, .. }, _) if !noise.tiles() => {
+                return Err(format!("{:?} doesn't tile", noise));
+            }
+            (Generator::Noise { noise, scale }, TextureKind::D2) if depth == 1 => {
+                let noise = noise.build(seed);
+                let data = bake::noise_texture_2d((res.0, res.1), scale.truncate(), true, &noise);
+                bake::encode(&data, encoding)
+            }
+            (Generator::Noise { noise, scale }, TextureKind::D3) => {
+                let noise = noise.build(seed);
+                bake::encode(&bake::noise_texture_3d(res, *scale, true, &noise), encoding)
+            }
+            (Generator::CloudShape { basis, .. }, TextureKind::D3) if !basis.tiles() => {
+                return Err(format!("a {:?} basis can't tile", basis));
+            }
+            (Generator::CloudShape { frequency, basis }, TextureKind::D3) => {
+                let basis = basis.basis();
+                bake::encode_rgba(
+                    &bake::rgba_texture_3d(res, Vec3::splat(*frequency), true, |p, period| {
+                        noise::cloud_shape(p, period.unwrap_or(noise::NO_PERIOD), seed, basis)
+                    }),
+                    encoding,
+                )
+            }
+            (Generator::CloudDetail { frequency }, TextureKind::D3) => bake::encode_rgba(
+                &bake::rgba_texture_3d(res, Vec3::splat(*frequency), true, |p, period| {
+                    noise::cloud_detail(p, period.unwrap_or(noise::NO_PERIOD), seed)
+                }),
+                encoding,
+            ),
+            (Generator::Curl { frequency }, TextureKind::D3) => bake::encode_rgba(
+                &bake::vector_texture_3d(res, Vec3::splat(*frequency), true, |p, period| {
+                    noise::curl_noise(p, period.unwrap_or(noise::NO_PERIOD), seed)
+                }),
+                encoding,
+            ),
+            (
+                Generator::LoopingFbm {
+                    scale,
+                    radius,
+                    octaves,
+                },
+                TextureKind::D2Array,
+            ) => bake::encode(
+                &bake::looping_texture_array(
+                    (res.0, res.1),
+                    res.2,
+                    *scale,
+                    *radius,
+                    |p, period| noise::perlin_fbm4(p, period, *octaves, seed),
+                ),
+                encoding,
+            ),
+            (Generator::Sdf { sdf }, TextureKind::D3) => {
+                let distance = noise::FromFn(|p: Vec3, _: Option<Vec3>| sdf.distance(p - 1.0));
+                bake::encode(
+                    &bake::noise_texture_3d(res, Vec3::splat(2.0), false, &distance),
+                    encoding,
+                )
+            }
+            (Generator::Graph { path, .. } | Generator::SdfFile { path }, _) => {
+                return Err(format!("{} has to be inlined before baking", path));
+            }
+            (generator, dimension) => {
+                return Err(format!(
+                    "can't generate a {:?} {:?} texture with {:?}",
+                    dimension, self.resolution, generator
+                ));
+            }
+        };
+        let dimension = match self.dimension {
+            TextureKind::D3 => TextureDimension::D3,
+            _ => TextureDimension::D2,
+        };
+        let mut baked = Baked::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: depth,
+            },
+            dimension,
+            encoded,
+        );
+        let tiles = !matches!(self.generator, Generator::Sdf { .. });
+        if !tiles {
+            baked.image.sampler_descriptor = ImageSampler::Descriptor(SamplerDescriptor {
+                address_mode_u: AddressMode::ClampToEdge,
+                address_mode_v: AddressMode::ClampToEdge,
+                address_mode_w: AddressMode::ClampToEdge,
+                ..ImageSampler::linear_descriptor()
+            });
+        }
+        mips::generate_mips(&mut baked.image, MipFilter::Kaiser, tiles);
+        return Ok(baked);
+    }
+}
+
+/// Name of the prebaked copy of the `.proctex.ron` file at `path`, `clouds/worley` for
+/// `clouds/worley.proctex.ron`, see [`prebaked::path`].
+pub fn prebaked_name(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    return path
+        .strip_suffix(".proctex.ron")
+        .unwrap_or(&path)
+        .to_string();
+}
+
+pub struct ProcTexLoader {
+    features: WgpuFeatures,
+    seed: Arc<AtomicU32>,
+}
+
+impl FromWorld for ProcTexLoader {
+    fn from_world(world: &mut World) -> Self {
+        // no device without the render plugin, bake the file's format as is then
+        let features = world
+            .get_resource::<RenderDevice>()
+            .map_or(WgpuFeatures::all(), |device| device.features());
+        let seed = world.resource::<LoaderSeed>().0.clone();
+        Self { features, seed }
+    }
+}
+
+impl AssetLoader for ProcTexLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let mut texture: ProcTex = ron::de::from_bytes(bytes)?;
+            if let Some(path) = texture.reads() {
+                // read through the context so editing the file reloads this one
+                let bytes = load_context.read_asset_bytes(path).await?;
+                texture.inline_graph(&bytes)?;
+                texture
+                    .inline_sdf(&bytes)
+                    .map_err(bevy::asset::Error::msg)?;
+            }
+            texture.format = texture.format.supported(self.features);
+            let seed = NoiseSeed(self.seed.load(Ordering::Relaxed));
+            let name = prebaked_name(load_context.path());
+            let key = texture.bake_key(seed);
+            let placeholder = texture.placeholder();
+            let (image, range, placeholder) =
+                match prebaked::load(load_context.asset_io(), &name, key).await {
+                    Some((image, range)) => (image, range, false),
+                    None => {
+                        // fails like the full bake would, it's the same texture
+                        let baked = placeholder.bake(seed).map_err(bevy::asset::Error::msg)?;
+                        let smaller = placeholder.resolution != texture.resolution;
+                        (baked.image, baked.range, smaller)
+                    }
+                };
+            load_context.set_labeled_asset("range", LoadedAsset::new(DecodeRange(range)));
+            load_context.set_labeled_asset(
+                "texture",
+                LoadedAsset::new(LoadedProcTex {
+                    texture,
+                    seed,
+                    placeholder,
+                }),
+            );
+            load_context.set_default_asset(LoadedAsset::new(image));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["proctex.ron"]
+    }
+}
+
+/// What `image` was loaded from, if it's a `.proctex.ron` file.
+pub fn loaded_from<'a>(
+    asset_server: &AssetServer,
+    textures: &'a Assets<LoadedProcTex>,
+    image: &Handle<Image>,
+) -> Option<&'a LoadedProcTex> {
+    let path = asset_server.get_handle_path(image)?;
+    let texture = HandleId::from(AssetPath::new_ref(path.path(), Some("texture")));
+    return textures.get(&Handle::weak(texture));
+}
+
+// The image a labeled asset of a `.proctex.ron` file was loaded along with
+fn loaded_image(asset_server: &AssetServer, label: HandleId) -> Option<Handle<Image>> {
+    let path = asset_server.get_handle_path(label)?;
+    let image = HandleId::from(AssetPath::new_ref(path.path(), None));
+    return Some(Handle::weak(image));
+}
+
+// Hands the range of every (re)loaded texture to PendingBakes under the image's handle,
+// and the placeholders' full bakes. Both labeled assets land in the same frame as the
+// image, so the bake can't be overwritten by its own placeholder.
+fn finish_loads(
+    mut range_events: EventReader<AssetEvent<DecodeRange>>,
+    mut texture_events: EventReader<AssetEvent<LoadedProcTex>>,
+    ranges: Res<Assets<DecodeRange>>,
+    textures: Res<Assets<LoadedProcTex>>,
+    asset_server: Res<AssetServer>,
+    mut pending: ResMut<PendingBakes>,
+) {
+    for event in range_events.iter() {
+        let (AssetEvent::Created { handle } | AssetEvent::Modified { handle }) = event else {
+            continue;
+        };
+        let (Some(range), Some(image)) =
+            (ranges.get(handle), loaded_image(&asset_server, handle.id()))
+        else {
+            continue;
+        };
+        pending.set_range(&image, range.0);
+    }
+    for event in texture_events.iter() {
+        let (AssetEvent::Created { handle } | AssetEvent::Modified { handle }) = event else {
+            continue;
+        };
+        let (Some(loaded), Some(image)) = (
+            textures.get(handle),
+            loaded_image(&asset_server, handle.id()),
+        ) else {
+            continue;
+        };
+        // whatever was baking is older than this load
+        pending.cancel(&image);
+        if loaded.placeholder {
+            let LoadedProcTex { texture, seed, .. } = loaded.clone();
+            pending.try_replace(image, move || texture.bake(seed));
+        }
+    }
+}
+
+/// The app's [`NoiseSeed`], shared with the loader since it can't read resources.
+#[derive(Resource)]
+struct LoaderSeed(Arc<AtomicU32>);
+
+// Re-bakes every texture when the seed changes, they all know their range
+fn reseed(
+    seed: Res<NoiseSeed>,
+    loader_seed: Res<LoaderSeed>,
+    ranges: Res<Assets<DecodeRange>>,
+    asset_server: Res<AssetServer>,
+) {
+    if !seed.is_changed() {
+        return;
+    }
+    loader_seed.0.store(seed.0, Ordering::Relaxed);
+    if seed.is_added() {
+        return;
+    }
+    for (id, _) in ranges.iter() {
+        if let Some(path) = asset_server.get_handle_path(id) {
+            asset_server.reload_asset(path.path());
+        }
+    }
+}
+
+/// Registers [`ProcTexLoader`] for `.proctex.ron` files.
+pub struct ProcTexPlugin;
+
+impl Plugin for ProcTexPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NoiseSeed>();
+        let seed = app.world.resource::<NoiseSeed>().0;
+        app.insert_resource(LoaderSeed(Arc::new(AtomicU32::new(seed))))
+            .add_asset::<DecodeRange>()
+            .add_asset::<LoadedProcTex>()
+            .init_asset_loader::<ProcTexLoader>()
+            .add_system(finish_loads)
+            .add_system(reseed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec3;
+
+    use super::*;
+
+    fn read(path: &str) -> Vec<u8> {
+        return std::fs::read(std::path::Path::new("assets").join(path)).unwrap();
+    }
+
+    #[test]
+    fn graphs_bake_like_inline_noise() {
+        let mut texture: ProcTex = ron::de::from_bytes(&read("clouds/worley.proctex.ron")).unwrap();
+        texture.resolution = (32, 32, 1);
+        assert!(
+            texture.bake(NoiseSeed(0)).is_err(),
+            "baked without its graph"
+        );
+        let Generator::Graph { path, scale } = texture.generator.clone() else {
+            panic!("worley.proctex.ron doesn't read a graph");
+        };
+        let graph = read(&path);
+        texture.inline_graph(&graph).unwrap();
+
+        let graph: NoiseGraph = ron::de::from_bytes(&graph).unwrap();
+        let inline = ProcTex {
+            generator: Generator::Noise {
+                noise: graph.root,
+                scale,
+            },
+            ..texture.clone()
+        };
+        let seed = NoiseSeed(4);
+        assert_eq!(
+            texture.bake(seed).unwrap().image.data,
+            inline.bake(seed).unwrap().image.data
+        );
+    }
+
+    #[test]
+    fn seed_offsets_the_app_seed() {
+        let texture = |seed| ProcTex {
+            generator: Generator::Noise {
+                noise: NoiseNode::Noised,
+                scale: Vec3::splat(2.0),
+            },
+            resolution: (16, 16, 1),
+            dimension: TextureKind::D2,
+            format: bake::TexelEncoding::Float32,
+            seed,
+        };
+        let bytes = |texture: ProcTex, seed| texture.bake(NoiseSeed(seed)).unwrap().image.data;
+        assert_eq!(bytes(texture(2), 5), bytes(texture(0), 7));
+        assert_ne!(bytes(texture(2), 5), bytes(texture(0), 5));
+    }
+
+    #[test]
+    fn bake_key_follows_what_is_baked() {
+        let texture = ProcTex {
+            generator: Generator::Noise {
+                noise: NoiseNode::Noised,
+                scale: Vec3::splat(2.0),
+            },
+            resolution: (16, 16, 1),
+            dimension: TextureKind::D2,
+            format: bake::TexelEncoding::Unorm16,
+            seed: 2,
+        };
+        let key = texture.bake_key(NoiseSeed(5));
+        assert_eq!(key.seed, NoiseSeed(7));
+        // prebaked copies are float and may be baked at another resolution
+        let resized = ProcTex {
+            resolution: (64, 64, 1),
+            format: bake::TexelEncoding::Float32,
+            ..texture.clone()
+        };
+        assert_eq!(resized.bake_key(NoiseSeed(5)), key);
+        let edited = ProcTex {
+            generator: Generator::Noise {
+                noise: NoiseNode::Noised,
+                scale: Vec3::splat(3.0),
+            },
+            ..texture.clone()
+        };
+        assert_ne!(edited.bake_key(NoiseSeed(5)).params, key.params);
+    }
+
+    #[test]
+    fn only_tiling_noise_bakes() {
+        let shape: ProcTex = ron::de::from_bytes(&read("clouds/shape.proctex.ron")).unwrap();
+        let simplex = ProcTex {
+            generator: Generator::CloudShape {
+                frequency: 4.0,
+                basis: BasisKind::Simplex,
+            },
+            resolution: (8, 8, 8),
+            ..shape
+        };
+        assert!(simplex.bake(NoiseSeed(0)).is_err());
+        let graph = ProcTex {
+            generator: Generator::Noise {
+                noise: NoiseNode::Abs(Box::new(NoiseNode::Simplex)),
+                scale: Vec3::splat(2.0),
+            },
+            ..simplex
+        };
+        assert!(graph.bake(NoiseSeed(0)).is_err());
+    }
+
+    #[test]
+    fn placeholders_are_smaller_copies() {
+        let load = |path: &str| -> ProcTex { ron::de::from_bytes(&read(path)).unwrap() };
+        let worley = load("clouds/worley.proctex.ron");
+        let placeholder = worley.placeholder();
+        assert_eq!(placeholder.resolution, (32, 32, 1));
+        assert_eq!(
+            placeholder.bake_key(NoiseSeed(3)),
+            worley.bake_key(NoiseSeed(3))
+        );
+        // every frame of the animation, every texel of what's already small
+        assert_eq!(
+            load("clouds/animated.proctex.ron").placeholder().resolution,
+            (32, 32, 16)
+        );
+        assert_eq!(
+            load("clouds/shape.proctex.ron").placeholder().resolution,
+            (32, 32, 32)
+        );
+        let detail = load("clouds/detail.proctex.ron");
+        assert_eq!(detail.placeholder().resolution, detail.resolution);
+    }
+
+    #[test]
+    fn fractals_tune_the_inlined_graph() {
+        let inlined = |path: &str| -> ProcTex {
+            let mut texture: ProcTex = ron::de::from_bytes(&read(path)).unwrap();
+            assert!(texture.fractal_mut().is_none(), "tuned before inlining");
+            let Generator::Graph { path, .. } = texture.generator.clone() else {
+                panic!("{} doesn't read a graph", path);
+            };
+            texture.inline_graph(&read(&path)).unwrap();
+            texture.resolution = (16, 16, 1);
+            return texture;
+        };
+        // the graph files start out as the fractals the cloud tunes
+        let mut value = inlined("clouds/value.proctex.ron");
+        assert_eq!(
+            value.fractal_mut().copied(),
+            Some(FractalSettings::value_fbm())
+        );
+        let mut worley = inlined("clouds/worley.proctex.ron");
+        assert_eq!(worley.fractal_mut().copied(), Some(FractalSettings::wfbm()));
+
+        let mut tuned = worley.clone();
+        tuned.fractal_mut().unwrap().octaves = 2;
+        let bytes = |texture: &ProcTex| texture.bake(NoiseSeed(0)).unwrap().image.data;
+        assert_ne!(bytes(&tuned), bytes(&worley));
+        tuned.fractal_mut().unwrap().octaves = 3;
+        assert_eq!(bytes(&tuned), bytes(&worley));
+    }
+
+    #[test]
+    fn distance_fields_bake_their_file() {
+        let mut texture: ProcTex = ron::de::from_bytes(&read("clouds/sdf.proctex.ron")).unwrap();
+        texture.resolution = (8, 8, 8);
+        assert!(
+            texture.bake(NoiseSeed(0)).is_err(),
+            "baked without its file"
+        );
+        let path = texture.reads().unwrap().to_string();
+        texture.inline_sdf(&read(&path)).unwrap();
+
+        let sdf = SdfNode::from_ron(&read(&path)).unwrap();
+        let baked = texture.bake(NoiseSeed(0)).unwrap();
+        let texel =
+            |i: usize| f32::from_ne_bytes(baked.image.data[i * 4..i * 4 + 4].try_into().unwrap());
+        assert_eq!(texel(0), sdf.distance(Vec3::splat(-1.0)));
+        assert_eq!(texel(1 + 8 * 6), sdf.distance(vec3(-0.75, 0.5, -1.0)));
+        let ImageSampler::Descriptor(sampler) = &baked.image.sampler_descriptor else {
+            panic!("a distance field sampled like the tiling textures");
+        };
+        assert_eq!(sampler.address_mode_w, AddressMode::ClampToEdge);
+
+        let mut flat = texture.clone();
+        flat.dimension = TextureKind::D2;
+        assert!(flat.bake(NoiseSeed(0)).is_err());
+        let mut degenerate = ProcTex {
+            generator: Generator::SdfFile { path },
+            ..texture
+        };
+        let transform = "Transform(input: Sphere(radius: 1.0), scale: NaN)";
+        assert!(degenerate.inline_sdf(transform.as_bytes()).is_err());
+    }
+}
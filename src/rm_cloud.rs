@@ -1,28 +1,28 @@
-use antidote::Mutex;
-use std::cell::Cell;
-use std::f32::consts::E;
-
-use crate::noise::fbmd;
-use crate::sdf::sdf as cloud_sdf;
-use crate::{noise, CameraController};
+use crate::CameraController;
 use bevy::{
     math::{vec3, vec4},
     prelude::*,
     reflect::TypeUuid,
     render::render_resource::{AsBindGroup, Extent3d, ShaderRef, TextureDimension, TextureFormat},
 };
-use itertools::Itertools;
-use rayon::prelude::*;
+use resume::{
+    bake,
+    noise::{self, NoiseSeed},
+};
 
 #[derive(Component, Default)]
 struct RMCloud {
     handle: Handle<RMCloudMaterial>,
 }
 
+// not added in main.rs, cloud::RMCloudPlugin replaced it
+#[allow(dead_code)]
 pub struct RMCloudPlugin;
+#[allow(dead_code)]
 impl Plugin for RMCloudPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(MaterialPlugin::<RMCloudMaterial>::default());
+        app.init_resource::<NoiseSeed>();
         app.add_system(
             |cam: Query<&Transform, With<CameraController>>,
              clouds: Query<(&RMCloud, &Transform)>,
@@ -35,6 +35,7 @@ impl Plugin for RMCloudPlugin {
                         material.time = time.raw_elapsed_seconds();
                         material.aabb_position = transform.translation;
                         material.scale = transform.scale;
+                        material.frame = material.frame.wrapping_add(1);
                     }
                 }
             },
@@ -47,14 +48,15 @@ impl Plugin for RMCloudPlugin {
              // mut materials: ResMut<Assets<StandardMaterial>>,
              mut cloud_materials: ResMut<Assets<RMCloudMaterial>>,
              // mut noise_materials: ResMut<Assets<NoiseMaterial>>,
-             mut images: ResMut<Assets<Image>>| {
+             mut images: ResMut<Assets<Image>>,
+             seed: Res<NoiseSeed>| {
                 {
                     let res = [1000, 1, 1000];
                     let resf = vec3(res[0] as f32, res[1] as f32, res[2] as f32);
 
-                    let sdf_data = new_cloud_data(res)
+                    let sdf_data = new_cloud_data(res, *seed)
                         .iter()
-                        .map(|v| {
+                        .flat_map(|v| {
                             [
                                 v.x.to_ne_bytes(),
                                 v.y.to_ne_bytes(),
@@ -63,7 +65,6 @@ impl Plugin for RMCloudPlugin {
                             ]
                         })
                         .flatten()
-                        .flatten()
                         .collect::<Vec<u8>>();
                     let texture = images.add(Image::new(
                         Extent3d {
@@ -75,8 +76,20 @@ impl Plugin for RMCloudPlugin {
                         sdf_data,
                         TextureFormat::Rgba32Float,
                     ));
+                    let blue_res = 64;
+                    let blue_noise = images.add(Image::new(
+                        Extent3d {
+                            width: blue_res as u32,
+                            height: blue_res as u32,
+                            depth_or_array_layers: 1,
+                        },
+                        TextureDimension::D2,
+                        bake::r32_bytes(&noise::blue_noise_2d((blue_res, blue_res), *seed)),
+                        TextureFormat::R32Float,
+                    ));
                     let material = cloud_materials.add(RMCloudMaterial {
                         sdf: Some(texture.clone()),
+                        blue_noise: Some(blue_noise),
                         texture_dimensions: vec3(res[0] as f32, res[1] as f32, res[2] as f32),
                         sun_direction: vec3(1., 1., 0.).normalize(),
                         ..default()
@@ -109,46 +122,40 @@ impl Plugin for RMCloudPlugin {
 
 // This chunk will cover just a single octant of a sphere SDF (radius 15).
 
-pub fn new_cloud_data(buffer_dimensions: [usize; 3]) -> Vec<Vec4> {
+pub fn new_cloud_data(buffer_dimensions: [usize; 3], seed: NoiseSeed) -> Vec<Vec4> {
     let resolution = vec3(
         buffer_dimensions[0] as f32,
         buffer_dimensions[1] as f32,
         buffer_dimensions[2] as f32,
     );
+    // x outermost, z innermost
+    let coord = |i: usize| {
+        [
+            i / (buffer_dimensions[1] * buffer_dimensions[2]),
+            i / buffer_dimensions[2] % buffer_dimensions[1],
+            i % buffer_dimensions[2],
+        ]
+    };
+    let index = |[x, y, z]: [usize; 3]| -> Option<usize> {
+        (x < buffer_dimensions[0] && y < buffer_dimensions[1] && z < buffer_dimensions[2])
+            .then(|| (x * buffer_dimensions[1] + y) * buffer_dimensions[2] + z)
+    };
+    let len = buffer_dimensions[0] * buffer_dimensions[1] * buffer_dimensions[2];
 
-    let data = Vec::from_iter((0..buffer_dimensions[0]).map(|_| {
-        (0..buffer_dimensions[1])
-            .map(|_| {
-                (0..buffer_dimensions[2])
-                    .map(|_| Mutex::new(vec4(1., 1., 1., 1.)))
-                    .collect_vec()
-            })
-            .collect_vec()
-    }));
-    (0..buffer_dimensions[0])
-        .flat_map(move |x| {
-            (0..buffer_dimensions[1])
-                .flat_map(move |y| (0..buffer_dimensions[2]).map(move |z| (x, y, z)))
-        })
-        .par_bridge()
-        .for_each(|(x, y, z)| {
-            let p = coord_to_pos([x, y, z], resolution);
+    let density = bake::par_fill(len, |start, chunk| {
+        for (i, texel) in chunk.iter_mut().enumerate() {
+            let p = coord_to_pos(coord(start + i), resolution);
             // let d = cloud_sdf(p);
             let sca = vec3(0.50, 0.50, 0.50) / 100.0 * resolution;
-            let n = ((noise::wfbm(p * sca, Vec3::ONE * 1000.0)
-                * (2.0 + fbmd(p * sca + 110.1231231).x)
+            let n = ((noise::wfbm(p * sca, Vec3::ONE * 1000.0, seed)
+                * (2.0 + noise::fbmd(p * sca + 110.123_12, seed).x)
                 * 0.5)/*
              * ((1.0 - (-4.0 * (p.y + 1.0)).exp()) * ((-p.y).exp() - 0.37))*/)
                 .clamp(0.0, 3.0);
-            *data[x][y][z].lock() = vec4(n, 0., n, 0.);
-        });
+            *texel = n;
+        }
+    });
 
-    // for x in 0..buffer_dimensions[0] {
-    //     for y in 0..buffer_dimensions[1] {
-    //         for z in 0..buffer_dimensions[2] {
-    //         }
-    //     }
-    // }
     // Sun light info requires sdf and density info
 
     let sun_base = vec3(-0., 2., 0.).normalize();
@@ -168,31 +175,22 @@ pub fn new_cloud_data(buffer_dimensions: [usize; 3]) -> Vec<Vec4> {
 
     // Sun raymarching
 
-    (0..buffer_dimensions[0])
-        .flat_map(|x| {
-            (0..buffer_dimensions[1])
-                .flat_map(move |y| (0..buffer_dimensions[2]).map(move |z| (x, y, z)))
-        })
-        .par_bridge()
-        .for_each(|(x, y, z)| {
+    bake::par_fill(len, |start, chunk| {
+        for (i, texel) in chunk.iter_mut().enumerate() {
+            let coord = coord(start + i);
             let mut total = 0.;
             let dt = 2. / resolution.x.min(resolution.y).min(resolution.z) * 0.1;
             for (sun_direction, phase) in sun_directions.iter() {
                 let mut t = 1.;
-                let mut p = coord_to_pos([x, y, z], resolution);
-                let mut sample_point = [x, y, z];
-                while let Some(samp) = data
-                    .get(sample_point[0])
-                    .and_then(|slice| slice.get(sample_point[1]))
-                    .and_then(|row| row.get(sample_point[2]))
-                {
-                    let samp = samp.lock();
+                let mut p = coord_to_pos(coord, resolution);
+                let mut sample_point = coord;
+                while let Some(samp) = index(sample_point).map(|i| density[i]) {
                     if p.x.abs() > 1. || p.y.abs() > 1. || p.z.abs() > 1. {
                         break;
                     }
 
                     let dm = 0.5;
-                    let noise = 0.0_f32.max(samp.z - dm);
+                    let noise = 0.0_f32.max(samp - dm);
                     // if noise > 0.0 {
                     t += noise * dt * 5.0;
                     // println!("{noise} {t}");
@@ -202,22 +200,13 @@ pub fn new_cloud_data(buffer_dimensions: [usize; 3]) -> Vec<Vec4> {
                 }
                 total += t;
             }
-            data[x][y][z].lock().y = total / sun_directions.len() as f32;
-        });
-    data.iter()
-        .flat_map(|row_col| {
-            row_col
-                .iter()
-                .flat_map(|row| row.iter().map(|c| c.lock().clone()))
-        })
-        .collect_vec()
-}
-
-#[allow(dead_code)]
-fn rotate(v: Vec3, x: f32, y: f32, z: f32) -> Vec3 {
-    Mat3::from_euler(bevy::prelude::EulerRot::XYZ, x, y, z) * v
+            let n = density[start + i];
+            *texel = vec4(n, total / sun_directions.len() as f32, n, 0.);
+        }
+    })
 }
 
+#[allow(clippy::excessive_precision)]
 fn mie(costh: f32) -> f32 {
     // This function was optimized to minimize (delta*delta)/reference in order to capture
     // the low intensity behavior.
@@ -246,8 +235,8 @@ fn mie(costh: f32) -> f32 {
     return exp_values.dot(exp_val_weight) * 0.25;
 }
 
-pub fn coord_to_pos<T: AsF32 + Copy>(coord: [T; 3], res: Vec3) -> Vec3 {
-    (vec3(coord[0].as_f32(), coord[1].as_f32(), coord[2].as_f32()) / res - 0.5) * 2.
+pub fn coord_to_pos(coord: [usize; 3], res: Vec3) -> Vec3 {
+    (vec3(coord[0] as f32, coord[1] as f32, coord[2] as f32) / res - 0.5) * 2.
 }
 
 pub fn pos_to_coord(p: Vec3, res: Vec3) -> [usize; 3] {
@@ -259,25 +248,6 @@ pub fn pos_to_coord(p: Vec3, res: Vec3) -> [usize; 3] {
     ]
 }
 
-pub trait AsF32 {
-    fn as_f32(self) -> f32;
-}
-impl AsF32 for f32 {
-    fn as_f32(self) -> f32 {
-        self
-    }
-}
-impl AsF32 for u32 {
-    fn as_f32(self) -> f32 {
-        self as f32
-    }
-}
-impl AsF32 for usize {
-    fn as_f32(self) -> f32 {
-        self as f32
-    }
-}
-
 /// The Material trait is very configurable, but comes with sensible defaults for all methods.
 /// You only need to implement functions for features that need non-default behavior. See the Material api docs for details!
 impl Material for RMCloudMaterial {
@@ -306,20 +276,11 @@ pub struct RMCloudMaterial {
     pub scale: Vec3,
     #[uniform(0)]
     pub time: f32,
+    #[uniform(0)]
+    pub frame: u32,
     #[texture(1, dimension = "3d")]
     #[sampler(2)]
     pub sdf: Option<Handle<Image>>,
-}
-
-#[derive(AsBindGroup, TypeUuid, Debug, Clone, Default, Reflect)]
-#[uuid = "f692fd8e-d598-45ab-8225-97e2a3f056e0"]
-pub struct GodRayMaterial {
-    #[uniform(0)]
-    pub sun_direction: Vec3,
-    #[uniform(0)]
-    pub camera_position: Vec3,
-    #[uniform(0)]
-    pub scale: Vec3,
-    #[uniform(0)]
-    pub time: f32,
+    #[texture(3)]
+    pub blue_noise: Option<Handle<Image>>,
 }
@@ -1,9 +1,174 @@
-#![allow(dead_code)]
-pub fn sdf(position: Vec3) -> f32 {
-    let d = sd_ellipsoid(position, vec3(0.5, 0.0, 0.5));
-    let d = ((position - vec3(0., 0.2, 0.)).length() - 0.3).min(d);
-    let fbm = sd_fbm(position + vec3(-100.123, 303.13, 634.23), d, 20);
-    fbm // d
+//! Signed distance fields described as data: a tree of [`SdfNode`]s, so cloud shapes can
+//! be authored in `.sdf.ron` files instead of hard-coded. Parse them with
+//! [`SdfNode::from_ron`], which rejects the degenerate shapes whose distances would divide
+//! by zero or come out NaN. The cloud material reads `assets/sdf/cloud.sdf.ron` baked into
+//! a volume, see [`crate::proctex::Generator::SdfFile`].
+
+/// A node of a distance field. Distances are exact for the primitives, bounds for the
+/// rest.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum SdfNode {
+    Sphere {
+        radius: f32,
+    },
+    Box {
+        half_size: Vec3,
+    },
+    /// Every radius has to be positive and finite, the distance divides by them
+    Ellipsoid {
+        radii: Vec3,
+    },
+    /// A segment from `a` to `b` thickened by `radius`, `a` and `b` have to differ
+    Capsule {
+        a: Vec3,
+        b: Vec3,
+        radius: f32,
+    },
+    /// Around the y axis
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+    },
+
+    Union(Vec<SdfNode>),
+    /// Union blending over a distance of about `k`, which has to be positive and finite
+    SmoothUnion {
+        inputs: Vec<SdfNode>,
+        k: f32,
+    },
+    /// `a` with `b` cut out of it
+    Subtraction(Box<SdfNode>, Box<SdfNode>),
+    Intersection(Vec<SdfNode>),
+
+    /// The input moved, rotated and scaled. The scale is uniform so distances stay exact,
+    /// and has to be positive and finite, the sample point is divided by it. The rotation
+    /// has to be normalised
+    Transform {
+        input: Box<SdfNode>,
+        #[serde(default)]
+        translation: Vec3,
+        #[serde(default)]
+        rotation: Quat,
+        #[serde(default = "one")]
+        scale: f32,
+    },
+    /// [`sd_fbm`] around the input, sampled at `p + offset`
+    Fbm {
+        input: Box<SdfNode>,
+        octaves: i32,
+        #[serde(default)]
+        offset: Vec3,
+    },
+}
+
+fn one() -> f32 {
+    return 1.0;
+}
+
+impl SdfNode {
+    /// Parses a tree and [`check`](Self::check)s it.
+    pub fn from_ron(bytes: &[u8]) -> Result<SdfNode, String> {
+        let node: SdfNode = ron::de::from_bytes(bytes).map_err(|e| e.to_string())?;
+        node.check()?;
+        return Ok(node);
+    }
+
+    /// Errors on the nodes whose distances would divide by zero or be NaN: ellipsoids
+    /// with a radius and smooth unions with a `k` that isn't positive and finite, capsules
+    /// from a point to itself, and transforms with a scale that isn't positive and finite,
+    /// a rotation that isn't normalised or a translation that isn't finite.
+    pub fn check(&self) -> Result<(), String> {
+        let inputs: Vec<&SdfNode> = match self {
+            SdfNode::Ellipsoid { radii } if !radii.is_finite() || radii.min_element() <= 0.0 => {
+                return Err(format!(
+                    "ellipsoid radii {} have to be positive and finite",
+                    radii
+                ));
+            }
+            SdfNode::Capsule { a, b, .. } if a == b => {
+                return Err(format!("capsule from {} to itself", a));
+            }
+            SdfNode::SmoothUnion { k, .. } if !k.is_finite() || *k <= 0.0 => {
+                return Err(format!(
+                    "smooth union k {} has to be positive and finite",
+                    k
+                ));
+            }
+            SdfNode::Transform { scale, .. } if !scale.is_finite() || *scale <= 0.0 => {
+                return Err(format!(
+                    "transform scale {} has to be positive and finite",
+                    scale
+                ));
+            }
+            SdfNode::Transform { rotation, .. } if !rotation.is_normalized() => {
+                return Err(format!("transform rotation {} isn't normalised", rotation));
+            }
+            SdfNode::Transform { translation, .. } if !translation.is_finite() => {
+                return Err(format!(
+                    "transform translation {} isn't finite",
+                    translation
+                ));
+            }
+            SdfNode::Sphere { .. }
+            | SdfNode::Box { .. }
+            | SdfNode::Ellipsoid { .. }
+            | SdfNode::Capsule { .. }
+            | SdfNode::Torus { .. } => vec![],
+            SdfNode::Union(inputs)
+            | SdfNode::SmoothUnion { inputs, .. }
+            | SdfNode::Intersection(inputs) => inputs.iter().collect(),
+            SdfNode::Subtraction(a, b) => vec![a, b],
+            SdfNode::Transform { input, .. } | SdfNode::Fbm { input, .. } => vec![input],
+        };
+        for input in inputs {
+            input.check()?;
+        }
+        return Ok(());
+    }
+
+    pub fn distance(&self, p: Vec3) -> f32 {
+        return match self {
+            SdfNode::Sphere { radius } => p.length() - radius,
+            SdfNode::Box { half_size } => sd_box(p, *half_size),
+            SdfNode::Ellipsoid { radii } => sd_ellipsoid(p, *radii),
+            SdfNode::Capsule { a, b, radius } => sd_capsule(p, *a, *b, *radius),
+            SdfNode::Torus {
+                major_radius,
+                minor_radius,
+            } => sd_torus(p, *major_radius, *minor_radius),
+
+            SdfNode::Union(inputs) => inputs
+                .iter()
+                .fold(f32::INFINITY, |d, input| d.min(input.distance(p))),
+            SdfNode::SmoothUnion { inputs, k } => inputs
+                .iter()
+                .map(|input| input.distance(p))
+                .reduce(|a, b| smooth_min(a, b, *k))
+                .unwrap_or(f32::INFINITY),
+            SdfNode::Subtraction(a, b) => a.distance(p).max(-b.distance(p)),
+            SdfNode::Intersection(inputs) => inputs
+                .iter()
+                .fold(f32::NEG_INFINITY, |d, input| d.max(input.distance(p))),
+
+            SdfNode::Transform {
+                input,
+                translation,
+                rotation,
+                scale,
+            } => input.distance(rotation.inverse() * (p - *translation) / *scale) * scale,
+            SdfNode::Fbm {
+                input,
+                octaves,
+                offset,
+            } => sd_fbm(p + *offset, input.distance(p), *octaves),
+        };
+    }
+}
+
+impl NoiseFn for SdfNode {
+    fn sample(&self, p: Vec3, _period: Option<Vec3>) -> f32 {
+        return self.distance(p);
+    }
 }
 
 const ROTATE: Mat3 = mat3(
@@ -17,6 +182,18 @@ fn sd_box(p: Vec3, b: Vec3) -> f32 {
     return q.max(vec3(0.0, 0.0, 0.0)).length() + q.x.max(q.y.max(q.z)).min(0.0);
 }
 
+fn sd_capsule(p: Vec3, a: Vec3, b: Vec3, r: f32) -> f32 {
+    let pa = p - a;
+    let ba = b - a;
+    let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+    return (pa - ba * h).length() - r;
+}
+
+fn sd_torus(p: Vec3, major: f32, minor: f32) -> f32 {
+    let q = vec2(p.xz().length() - major, p.y);
+    return q.length() - minor;
+}
+
 fn sd_grid_sphere(i: Vec3, f: Vec3, c: Vec3) -> f32 {
     // random radius at grid vertex i+c
     let has = 0.5 * hash43(i + c);
@@ -56,11 +233,23 @@ pub fn sd_fbm(p: Vec3, d: f32, octaves: i32) -> f32 {
         // prepare next octave
         p = ROTATE * p;
 
-        s = 0.5 * s;
+        s *= 0.5;
     }
     return d;
 }
 
+/// [`sd_fbm`] around the distance field `base`, as a [`NoiseFn`] so it can be baked.
+pub struct SdFbm<F> {
+    pub base: F,
+    pub octaves: i32,
+}
+
+impl<F: Fn(Vec3) -> f32 + Send + Sync> NoiseFn for SdFbm<F> {
+    fn sample(&self, p: Vec3, _period: Option<Vec3>) -> f32 {
+        sd_fbm(p, (self.base)(p), self.octaves)
+    }
+}
+
 fn mix(a: f32, b: f32, t: f32) -> f32 {
     a * (1. - t) + b * t
 }
@@ -75,7 +264,6 @@ fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
     return mix(b, a, h) - k * h * (1.0 - h);
 }
 
-#[allow(dead_code)]
 fn sd_ellipsoid(p: Vec3, r: Vec3) -> f32 {
     let k0 = (p / r).length();
     let k1 = (p / (r * r)).length();
@@ -89,6 +277,69 @@ fn hash43(p: Vec3) -> Vec4 {
 }
 
 use bevy::{
-    math::{mat3, vec3, vec4, Vec3Swizzles, Vec4Swizzles},
-    prelude::{Mat3, Vec3, Vec4},
+    math::{mat3, vec2, vec3, vec4, Vec3Swizzles, Vec4Swizzles},
+    prelude::{Mat3, Quat, Vec3, Vec4},
 };
+use serde::{Deserialize, Serialize};
+
+use crate::noise::NoiseFn;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sdf::sdf before the distance field became data
+    fn hard_coded(position: Vec3) -> f32 {
+        let d = sd_ellipsoid(position, vec3(0.5, 0.0, 0.5));
+        let d = ((position - vec3(0., 0.2, 0.)).length() - 0.3).min(d);
+        return sd_fbm(position + vec3(-100.123, 303.13, 634.23), d, 20);
+    }
+
+    #[test]
+    fn cloud_file_matches_the_hard_coded_cloud() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sdf/cloud.sdf.ron");
+        let file = SdfNode::from_ron(&std::fs::read(path).unwrap()).unwrap();
+        for i in 0..1000 {
+            let p = vec3((i % 10) as f32, (i / 10 % 10) as f32, (i / 100) as f32) * 0.2 - 1.0;
+            assert_eq!(file.distance(p), hard_coded(p), "cloud.sdf.ron at {}", p);
+        }
+    }
+
+    #[test]
+    fn rejects_divisions_by_zero() {
+        let degenerate = [
+            "Ellipsoid(radii: (0.5, 0.0, 0.5))",
+            "Capsule(a: (1.0, 0.0, 0.0), b: (1.0, 0.0, 0.0), radius: 0.1)",
+            "SmoothUnion(inputs: [], k: 0.0)",
+            "Transform(input: Sphere(radius: 1.0), scale: 0.0)",
+            "Union([Sphere(radius: 1.0), Transform(input: Sphere(radius: 1.0), scale: -1.0)])",
+            "Ellipsoid(radii: (0.5, NaN, 0.5))",
+            "SmoothUnion(inputs: [], k: NaN)",
+            "Ellipsoid(radii: (0.5, inf, 0.5))",
+            "Transform(input: Sphere(radius: 1.0), scale: NaN)",
+            "Transform(input: Sphere(radius: 1.0), scale: inf)",
+            "Transform(input: Sphere(radius: 1.0), rotation: (0.0, 0.0, 0.0, 2.0))",
+            "Transform(input: Sphere(radius: 1.0), rotation: (0.0, 0.0, 0.0, 0.0))",
+            "Transform(input: Sphere(radius: 1.0), rotation: (NaN, 0.0, 0.0, 1.0))",
+            "Transform(input: Sphere(radius: 1.0), translation: (inf, 0.0, 0.0))",
+        ];
+        for ron in degenerate {
+            assert!(
+                SdfNode::from_ron(ron.as_bytes()).is_err(),
+                "accepted {}",
+                ron
+            );
+        }
+        let fine =
+            "Subtraction(Box(half_size: (1.0, 1.0, 1.0)), Ellipsoid(radii: (0.5, 0.1, 0.5)))";
+        let node = SdfNode::from_ron(fine.as_bytes()).unwrap();
+        assert!(node.distance(vec3(0.9, 0.9, 0.9)) < 0.0);
+        assert!(node.distance(vec3(0.0, 0.05, 0.0)) > 0.0);
+        // a quarter turn about y, written out to the precision a file would have
+        let turned = "Transform(input: Box(half_size: (1.0, 0.1, 0.1)), \
+            rotation: (0.0, 0.7071068, 0.0, 0.7071068), scale: 2.0)";
+        let node = SdfNode::from_ron(turned.as_bytes()).unwrap();
+        assert!(node.distance(vec3(0.0, 0.0, 1.9)) < 0.0);
+        assert!(node.distance(vec3(1.9, 0.0, 0.0)) > 0.0);
+    }
+}
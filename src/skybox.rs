@@ -16,12 +16,22 @@ use bevy::{
     },
 };
 
-use crate::{noise, CameraController};
+use resume::{
+    bake,
+    mips::{self, MipFilter},
+    noise::{self, NoiseSeed},
+};
+
+use crate::CameraController;
 
+// commented out in main.rs
+#[allow(dead_code)]
 pub struct SkyBoxPlugin {}
 
+#[allow(dead_code)]
 impl Plugin for SkyBoxPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<NoiseSeed>();
         app.add_startup_system(setup);
         app.add_system(cycle_cubemap_asset);
         app.add_system(asset_loaded.after(cycle_cubemap_asset));
@@ -48,7 +58,9 @@ pub fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut images: ResMut<Assets<Image>>,
+    seed: Res<NoiseSeed>,
 ) {
+    let seed = *seed;
     let skybox_handle = asset_server.load(CUBEMAP.0);
 
     commands.insert_resource(Cubemap {
@@ -60,69 +72,49 @@ pub fn setup(
     const DIM: usize = 20;
     const VDIM: usize = 32;
     commands.insert_resource(NoiseTexture {
-        image_handle: images.add(Image::new(
-            Extent3d {
-                width: DIM as u32,
-                height: DIM as u32,
-                depth_or_array_layers: 1,
-            },
-            bevy::render::render_resource::TextureDimension::D2,
-            {
-                let mut data = Box::new([[0.0; DIM]; DIM]);
-                for x in 0..DIM {
-                    for y in 0..DIM {
-                        let n = noise::noised(
-                            Vec3 {
-                                x: x as f32,
-                                y: y as f32,
-                                z: 1.,
-                            } / 200.,
-                            Vec3::ONE * 10000.,
-                        )
-                        .x;
-                        data[x][y] = n;
-                        // println!("{n}");
-                    }
-                }
-                data.iter()
-                    .flatten()
-                    .map(|f| f.to_ne_bytes())
-                    .flatten()
-                    .collect()
-            },
-            bevy::render::render_resource::TextureFormat::R32Float,
+        image_handle: images.add(mips::with_mips(
+            Image::new(
+                Extent3d {
+                    width: DIM as u32,
+                    height: DIM as u32,
+                    depth_or_array_layers: 1,
+                },
+                bevy::render::render_resource::TextureDimension::D2,
+                bake::noise_texture_2d(
+                    (DIM, DIM),
+                    Vec2::splat(DIM as f32 / 200.),
+                    false,
+                    &noise::Noised { seed },
+                )
+                .iter()
+                .flat_map(|f| f.to_ne_bytes())
+                .collect(),
+                bevy::render::render_resource::TextureFormat::R32Float,
+            ),
+            MipFilter::Kaiser,
+            false,
         )),
-        volume_handle: images.add(Image::new(
-            Extent3d {
-                width: VDIM as u32,
-                height: VDIM as u32,
-                depth_or_array_layers: VDIM as u32,
-            },
-            bevy::render::render_resource::TextureDimension::D3,
-            {
-                let mut data = Box::new([[[0.0; VDIM]; VDIM]; VDIM]);
-                for x in 0..VDIM {
-                    for y in 0..VDIM {
-                        for z in 0..VDIM {
-                            data[x][y][z] = noise::worley_noise(
-                                Vec3 {
-                                    x: x as f32,
-                                    y: y as f32,
-                                    z: z as f32,
-                                } / 10.,
-                                Vec3::ONE * 100000.,
-                            );
-                        }
-                    }
-                }
-                data.iter()
-                    .flatten()
-                    .flatten()
-                    .map(|f| f.to_ne_bytes())
-                    .flatten()
-                    .collect()
-            },
-            bevy::render::render_resource::TextureFormat::R32Float,
+        volume_handle: images.add(mips::with_mips(
+            Image::new(
+                Extent3d {
+                    width: VDIM as u32,
+                    height: VDIM as u32,
+                    depth_or_array_layers: VDIM as u32,
+                },
+                bevy::render::render_resource::TextureDimension::D3,
+                bake::noise_texture_3d(
+                    (VDIM, VDIM, VDIM),
+                    Vec3::splat(VDIM as f32 / 10.),
+                    false,
+                    &noise::WorleyNoise { seed },
+                )
+                .iter()
+                .flat_map(|f| f.to_ne_bytes())
+                .collect(),
+                bevy::render::render_resource::TextureFormat::R32Float,
+            ),
+            MipFilter::Kaiser,
+            false,
         )),
     });
 }
@@ -164,6 +156,7 @@ pub fn cycle_cubemap_asset(
     cubemap.is_loaded = false;
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn asset_loaded(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
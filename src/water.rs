@@ -8,12 +8,16 @@ use bevy::{
 
 use crate::CameraController;
 
+// commented out in main.rs
+#[allow(dead_code)]
 pub struct WaterPlugin;
 
 #[derive(Component)]
 struct Water {
     handle: Handle<WaterMaterial>,
 }
+
+#[allow(dead_code)]
 impl Plugin for WaterPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(MaterialPlugin::<WaterMaterial>::default());